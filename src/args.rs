@@ -1,12 +1,62 @@
 use std::path::PathBuf;
 
-use crate::service_config::Service;
+use crate::cut_scoring::CutScoring;
+use crate::offset_mode::OffsetMode;
+use crate::output_format::OutputFormat;
+use crate::pause_mode::PauseMode;
 
 #[derive(clap::StructOpt)]
 #[clap(version)]
 pub struct Opts {
     pub output_dir: Option<PathBuf>,
-    pub service: Option<Service>,
+    /// The name of the service to record, either built-in or defined in the config file
+    pub service: Option<String>,
     #[clap(short, long)]
     pub monitor: bool,
+    /// What to do when the playback is paused while recording: end_session or split
+    #[clap(long)]
+    pub pause_mode: Option<PauseMode>,
+    /// How to determine the cut offsets: global or per_cut
+    #[clap(long)]
+    pub offset_mode: Option<OffsetMode>,
+    /// The format of the cut songs: opus, flac, mp3, vorbis or wav
+    #[clap(long)]
+    pub format: Option<OutputFormat>,
+    /// Bitrate (in bits per second) for lossy formats
+    #[clap(long)]
+    pub bitrate: Option<i64>,
+    /// Variable bitrate quality for mp3 and vorbis. Overrides the bitrate.
+    #[clap(long)]
+    pub vbr_quality: Option<f64>,
+    /// Compression level for flac
+    #[clap(long)]
+    pub compression_level: Option<u32>,
+    #[clap(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(clap::Subcommand)]
+pub enum Command {
+    /// Start the graphical user interface. This is the default if no command is given.
+    Gui,
+    /// Record a new session into the output directory without opening a window.
+    Record,
+    /// Cut a recorded session into songs using the automatically determined offset.
+    Cut {
+        /// Either the path to the session directory or its name within the output directory.
+        session: PathBuf,
+        /// How to find the cuts: volume, spectral_flux or energy_correlation.
+        /// Overrides the strategy stored in the session.
+        #[clap(long)]
+        cut_scoring: Option<CutScoring>,
+    },
+    /// List the recorded sessions in the output directory.
+    ListSessions,
+    /// Fix the buffer files of a session whose recording was not stopped cleanly.
+    Repair {
+        /// Either the path to the session directory or its name within the output directory.
+        session: PathBuf,
+    },
+    /// List the running MPRIS media players and their audio streams.
+    Discover,
 }
@@ -1,15 +1,17 @@
-use std::i16;
+use std::convert::TryFrom;
 
 use hound::WavSpec;
 use rodio::Source;
 
 use crate::audio_time::AudioTime;
+use crate::config::NUM_FRAMES_PER_AVERAGE_VOLUME;
 use crate::config::NUM_PLOT_DATA_POINTS;
-use crate::config::NUM_SAMPLES_PER_AVERAGE_VOLUME;
+use crate::config::QUIETEST_TIME_SEARCH_STEP;
+use crate::samples::Samples;
 
 #[derive(Clone)]
 pub struct AudioExcerpt {
-    pub samples: Vec<i16>,
+    pub samples: Samples,
     pub start: AudioTime,
     pub end: AudioTime,
     pub spec: WavSpec,
@@ -19,30 +21,129 @@ impl AudioExcerpt {
     pub fn get_volume_at(&self, time_f64: f64) -> f64 {
         let time = AudioTime::from_time_same_spec(time_f64, self.start);
         let position_exact = time - self.start;
-        let position_begin =
-            if position_exact.interleaved_sample_num < NUM_SAMPLES_PER_AVERAGE_VOLUME as u32 {
-                0
-            } else {
-                position_exact.interleaved_sample_num as usize - NUM_SAMPLES_PER_AVERAGE_VOLUME
-            };
+        let position = position_exact.frame_num as usize;
+        let position_begin = position.saturating_sub(NUM_FRAMES_PER_AVERAGE_VOLUME);
         let position_end = self
-            .samples
-            .len()
-            .min(position_exact.interleaved_sample_num as usize + NUM_SAMPLES_PER_AVERAGE_VOLUME);
+            .get_num_frames()
+            .min(position + NUM_FRAMES_PER_AVERAGE_VOLUME);
+        if position_end <= position_begin {
+            return 0.0;
+        }
         let inv_len = 1.0 / ((position_end - position_begin) as f64);
-        let inv_i16 = 1.0 / (i16::MAX as f64);
-        let average: f64 = self.samples[position_begin..position_end]
-            .iter()
-            .map(|x| (*x as f64).abs() * inv_len * inv_i16)
-            .sum::<f64>();
-        average
+        (position_begin..position_end)
+            .filter_map(|frame| self.samples.get_amplitude(frame, self.spec))
+            .map(|x| x as f64 * inv_len)
+            .sum::<f64>()
+    }
+
+    /// The average absolute amplitude over the entire excerpt.
+    pub fn get_average_volume(&self) -> f64 {
+        let num_frames = self.get_num_frames();
+        if num_frames == 0 {
+            return 0.0;
+        }
+        (0..num_frames)
+            .filter_map(|frame| self.samples.get_amplitude(frame, self.spec))
+            .map(|x| x as f64)
+            .sum::<f64>()
+            / num_frames as f64
+    }
+
+    /// The time of the zero crossing (of the samples averaged over all
+    /// channels) closest to the given time, if there is one within
+    /// max_distance seconds.
+    pub fn get_nearest_zero_crossing(&self, time: f64, max_distance: f64) -> Option<f64> {
+        let sample_rate = self.spec.sample_rate as f64;
+        let frame = ((time - self.start.time) * sample_rate).round() as i64;
+        let max_num_frames = (max_distance * sample_rate) as i64;
+        let get_sample = |frame: i64| {
+            usize::try_from(frame)
+                .ok()
+                .and_then(|frame| self.samples.get_downmixed(frame, self.spec))
+        };
+        let is_crossing = |frame: i64| match (get_sample(frame - 1), get_sample(frame)) {
+            (Some(before), Some(after)) => (before < 0.0) != (after < 0.0),
+            _ => false,
+        };
+        (0..=max_num_frames)
+            .flat_map(|distance| [frame - distance, frame + distance])
+            .find(|frame| is_crossing(*frame))
+            .map(|frame| self.start.time + frame as f64 / sample_rate)
+    }
+
+    /// The time within max_distance seconds of the
+    /// given time at which the volume is lowest.
+    pub fn get_quietest_time_near(&self, time: f64, max_distance: f64) -> f64 {
+        let num_steps = (max_distance / QUIETEST_TIME_SEARCH_STEP) as i64;
+        (-num_steps..=num_steps)
+            .map(|i| time + i as f64 * QUIETEST_TIME_SEARCH_STEP)
+            .filter(|time| self.start.time <= *time && *time <= self.end.time)
+            .map(|time| (self.get_volume_at(time), time))
+            .filter(|(volume, _)| volume.is_finite())
+            .min_by(|(volume1, _), (volume2, _)| volume1.total_cmp(volume2))
+            .map(|(_, time)| time)
+            .unwrap_or(time)
+    }
+
+    /// Appends the audio of another excerpt of the same buffer.
+    pub fn append(&mut self, other: AudioExcerpt) {
+        self.end = self.end + (other.end - other.start);
+        self.samples.append(other.samples);
+    }
+
+    /// If the excerpt ends in digital silence, such as the recording
+    /// of a paused player, returns the time at which the silence begins.
+    pub fn get_start_of_final_silence(&self) -> Option<f64> {
+        let num_frames = self.get_num_frames();
+        let is_silent = |frame: &usize| self.samples.is_silent(*frame, self.spec);
+        if num_frames == 0 || !is_silent(&(num_frames - 1)) {
+            return None;
+        }
+        let first_silent_frame = (0..num_frames)
+            .rev()
+            .find(|frame| !is_silent(frame))
+            .map(|frame| frame + 1)
+            .unwrap_or(0);
+        Some(self.start.time + first_silent_frame as f64 / self.spec.sample_rate as f64)
+    }
+
+    /// If the excerpt begins with digital silence, returns
+    /// the time at which the silence ends.
+    pub fn get_end_of_initial_silence(&self) -> Option<f64> {
+        let num_frames = self.get_num_frames();
+        let is_silent = |frame: &usize| self.samples.is_silent(*frame, self.spec);
+        if num_frames == 0 || !is_silent(&0) {
+            return None;
+        }
+        let first_audible_frame = (0..num_frames)
+            .find(|frame| !is_silent(frame))
+            .unwrap_or(num_frames);
+        Some(self.start.time + first_audible_frame as f64 / self.spec.sample_rate as f64)
+    }
+
+    pub fn get_num_frames(&self) -> usize {
+        self.samples.len() / self.spec.channels as usize
+    }
+
+    /// The normalized samples, averaged over all channels.
+    pub fn get_downmixed_samples(&self) -> Vec<f32> {
+        (0..self.get_num_frames())
+            .filter_map(|frame| self.samples.get_downmixed(frame, self.spec))
+            .collect()
+    }
+
+    /// The energy of every frame, averaged over all channels.
+    pub fn get_energies(&self) -> Vec<f32> {
+        (0..self.get_num_frames())
+            .filter_map(|frame| self.samples.get_energy(frame, self.spec))
+            .collect()
     }
 
     pub fn get_sample_times(&self) -> Vec<f32> {
         let width = self.end.time - self.start.time;
         let step_size = width as f32 / NUM_PLOT_DATA_POINTS as f32;
         (1..NUM_PLOT_DATA_POINTS)
-            .map(|x| self.start.time as f32 + (x as f32) * step_size as f32)
+            .map(|x| self.start.time as f32 + (x as f32) * step_size)
             .collect()
     }
 
@@ -54,6 +155,36 @@ impl AudioExcerpt {
             .collect()
     }
 
+    /// Splits the time range into (at most) num_peaks intervals and
+    /// returns the start time along with the minimum and maximum sample
+    /// (averaged over all channels) of each of them. Once the range
+    /// contains fewer frames than that, these are the samples themselves.
+    pub fn get_peaks(&self, start_time: f64, end_time: f64, num_peaks: usize) -> Vec<Peak> {
+        let sample_rate = self.spec.sample_rate as f64;
+        let get_frame = |time: f64| {
+            (((time - self.start.time) * sample_rate).max(0.0) as usize).min(self.get_num_frames())
+        };
+        let start_frame = get_frame(start_time);
+        let num_frames = get_frame(end_time).saturating_sub(start_frame);
+        let num_peaks = num_peaks.min(num_frames);
+        (0..num_peaks)
+            .map(|i| {
+                let begin = start_frame + i * num_frames / num_peaks;
+                let end = start_frame + (i + 1) * num_frames / num_peaks;
+                let (min, max) = (begin..end)
+                    .filter_map(|frame| self.samples.get_downmixed(frame, self.spec))
+                    .fold((f32::MAX, f32::MIN), |(min, max), sample| {
+                        (min.min(sample), max.max(sample))
+                    });
+                Peak {
+                    time: self.start.time + begin as f64 / sample_rate,
+                    min,
+                    max,
+                }
+            })
+            .collect()
+    }
+
     pub fn get_absolute_time_by_relative_progress(&self, pos: f64) -> AudioTime {
         AudioTime::from_time_and_spec(
             self.start.time + (self.end.time - self.start.time) * pos,
@@ -74,6 +205,11 @@ impl AudioExcerpt {
         0.5 + (time_offset / (self.end.time - self.start.time))
     }
 
+    pub fn get_time_offset_from_absolute_time(&self, absolute_time: f64) -> f64 {
+        // time_offset is relative to the center
+        absolute_time - (self.start.time + self.end.time) * 0.5
+    }
+
     pub fn get_absolute_time_from_time_offset(&self, time_offset: f64) -> AudioTime {
         self.get_absolute_time_by_relative_progress(
             self.get_relative_progress_from_time_offset(time_offset),
@@ -81,9 +217,16 @@ impl AudioExcerpt {
     }
 }
 
+pub struct Peak {
+    pub time: f64,
+    pub min: f32,
+    pub max: f32,
+}
+
 pub struct AudioExcerptSource {
     excerpt: AudioExcerpt,
     position: u32,
+    end: Option<u32>,
 }
 
 impl AudioExcerptSource {
@@ -91,6 +234,17 @@ impl AudioExcerptSource {
         Self {
             excerpt,
             position: start_time.interleaved_sample_num,
+            end: None,
+        }
+    }
+
+    /// Plays the excerpt from start_time up to end_time
+    /// (both relative to the start of the excerpt).
+    pub fn new_between(excerpt: AudioExcerpt, start_time: AudioTime, end_time: AudioTime) -> Self {
+        Self {
+            excerpt,
+            position: start_time.interleaved_sample_num,
+            end: Some(end_time.interleaved_sample_num),
         }
     }
 }
@@ -114,11 +268,138 @@ impl Source for AudioExcerptSource {
 }
 
 impl Iterator for AudioExcerptSource {
-    type Item = i16;
+    type Item = f32;
 
     fn next(&mut self) -> Option<Self::Item> {
-        let item = self.excerpt.samples.get(self.position as usize);
+        if self.end.map(|end| self.position >= end).unwrap_or(false) {
+            return None;
+        }
+        let item = self
+            .excerpt
+            .samples
+            .get_normalized(self.position as usize, self.excerpt.spec);
         self.position += 1;
-        item.copied()
+        item
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use hound::SampleFormat;
+    use hound::WavSpec;
+
+    use super::AudioExcerpt;
+    use crate::audio_time::AudioTime;
+    use crate::samples::Samples;
+
+    fn get_excerpt(samples: Vec<i32>) -> AudioExcerpt {
+        get_excerpt_with_sample_rate(samples, 10)
+    }
+
+    /// A stereo excerpt starting at 1s.
+    fn get_excerpt_with_sample_rate(samples: Vec<i32>, sample_rate: u32) -> AudioExcerpt {
+        let spec = WavSpec {
+            channels: 2,
+            sample_rate,
+            bits_per_sample: 16,
+            sample_format: SampleFormat::Int,
+        };
+        let num_frames = samples.len() / 2;
+        AudioExcerpt {
+            samples: Samples::Int(samples),
+            start: AudioTime::from_time_and_spec(1.0, spec),
+            end: AudioTime::from_time_and_spec(1.0 + num_frames as f64 / sample_rate as f64, spec),
+            spec,
+        }
+    }
+
+    #[test]
+    fn silence_at_edges() {
+        let excerpt = get_excerpt(vec![0, 0, 5, -5, 3, 0, 0, 0, 0, 0]);
+        assert_eq!(excerpt.get_end_of_initial_silence(), Some(1.1));
+        assert_eq!(excerpt.get_start_of_final_silence(), Some(1.3));
+        let excerpt = get_excerpt(vec![1, 0, 0, 0, 0, 1]);
+        assert_eq!(excerpt.get_end_of_initial_silence(), None);
+        assert_eq!(excerpt.get_start_of_final_silence(), None);
+    }
+
+    #[test]
+    fn append_excerpt() {
+        let mut excerpt = get_excerpt(vec![1, 1, 2, 2]);
+        excerpt.append(get_excerpt(vec![3, 3]));
+        assert_eq!(excerpt.get_num_frames(), 3);
+        assert!((excerpt.end.time - 1.3).abs() < 1e-9);
+    }
+
+    #[test]
+    fn channels_with_opposite_phase_are_not_silent() {
+        let excerpt = get_excerpt(vec![16384, -16384, -16384, 16384]);
+        assert_eq!(excerpt.get_downmixed_samples(), vec![0.0, 0.0]);
+        assert_eq!(excerpt.get_average_volume(), 0.5);
+        assert_eq!(excerpt.get_volume_at(1.0), 0.5);
+    }
+
+    #[test]
+    fn peaks_cover_the_range() {
+        let excerpt = get_excerpt(vec![16384, 16384, -16384, -16384, 8192, 8192, 0, 0]);
+        let peaks = excerpt.get_peaks(1.0, 1.45, 2);
+        assert_eq!(peaks.len(), 2);
+        assert!((peaks[0].time - 1.0).abs() < 1e-9);
+        assert_eq!((peaks[0].min, peaks[0].max), (-0.5, 0.5));
+        assert!((peaks[1].time - 1.2).abs() < 1e-9);
+        assert_eq!((peaks[1].min, peaks[1].max), (0.0, 0.25));
+        // There are never more peaks than frames.
+        assert_eq!(excerpt.get_peaks(1.0, 1.45, 10).len(), 4);
+    }
+
+    #[test]
+    fn nearest_zero_crossing() {
+        let excerpt = get_excerpt(vec![
+            16384, 16384, 8192, 8192, -8192, -8192, -16384, -16384, 16384, 16384,
+        ]);
+        let crossing = excerpt.get_nearest_zero_crossing(1.0, 0.5).unwrap();
+        assert!((crossing - 1.2).abs() < 1e-9);
+        let crossing = excerpt.get_nearest_zero_crossing(1.35, 0.5).unwrap();
+        assert!((crossing - 1.4).abs() < 1e-9);
+        assert_eq!(excerpt.get_nearest_zero_crossing(1.0, 0.1), None);
+    }
+
+    #[test]
+    fn quietest_time_near() {
+        // A second of audio at 44.1 kHz, silent from 1.5s to 1.6s.
+        let mut samples = vec![16384; 2 * 44100];
+        samples[2 * 22050..2 * 26460]
+            .iter_mut()
+            .for_each(|sample| *sample = 0);
+        let excerpt = get_excerpt_with_sample_rate(samples, 44100);
+        let quietest = excerpt.get_quietest_time_near(1.45, 0.2);
+        assert!(1.5 < quietest && quietest < 1.6);
+        assert_eq!(excerpt.get_volume_at(quietest), 0.0);
+    }
+
+    #[test]
+    fn quietest_time_near_skips_invalid_samples() {
+        // As above, but with NaN samples before the silence.
+        let spec = WavSpec {
+            channels: 2,
+            sample_rate: 44100,
+            bits_per_sample: 32,
+            sample_format: SampleFormat::Float,
+        };
+        let mut samples = vec![0.5; 2 * 44100];
+        samples[..2 * 13230]
+            .iter_mut()
+            .for_each(|sample| *sample = f32::NAN);
+        samples[2 * 22050..2 * 26460]
+            .iter_mut()
+            .for_each(|sample| *sample = 0.0);
+        let excerpt = AudioExcerpt {
+            samples: Samples::Float(samples),
+            start: AudioTime::from_time_and_spec(1.0, spec),
+            end: AudioTime::from_time_and_spec(2.0, spec),
+            spec,
+        };
+        let quietest = excerpt.get_quietest_time_near(1.35, 0.3);
+        assert!(1.5 < quietest && quietest < 1.6);
     }
 }
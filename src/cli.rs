@@ -0,0 +1,197 @@
+use std::path::Path;
+use std::path::PathBuf;
+
+use anyhow::anyhow;
+use anyhow::Result;
+use itertools::Itertools;
+
+use crate::config::MPRIS_BUS_NAME_PREFIX;
+use crate::cut::cut_song;
+use crate::cut::get_cut_info;
+use crate::cut::get_excerpt_collection;
+use crate::cut_scoring::CutScoring;
+use crate::offset_mode::OffsetMode;
+use crate::output_format::OutputSettings;
+use crate::pause_mode::PauseMode;
+use crate::recording::dbus::get_matching_services;
+use crate::recording::recorder;
+use crate::recording::recording_status::RecordingExitStatus;
+use crate::recording::recording_thread_handle::RecordingThreadHandle;
+use crate::recording_session::get_new_session_dir;
+use crate::recording_session::get_session_dirs;
+use crate::recording_session::RecordingSession;
+use crate::run_args::RunArgs;
+use crate::service_config::Service;
+use crate::service_config::ServiceConfig;
+use crate::sink_type::SinkType;
+
+pub fn record(
+    output_dir: &Path,
+    service: &Service,
+    sink_type: SinkType,
+    pause_mode: PauseMode,
+) -> Result<()> {
+    let session_dir = get_new_session_dir(output_dir);
+    let service_config = ServiceConfig::from_service(service)?;
+    let run_args = RunArgs::new(&session_dir, service_config, sink_type, pause_mode);
+    println!("Recording new session into {:?}", session_dir);
+    let (status, session) = RecordingThreadHandle::new(&run_args).get_result()?;
+    let reason = match status {
+        RecordingExitStatus::FinishedOrInterrupted => "playback stopped",
+        RecordingExitStatus::AlbumFinished => "album finished",
+        RecordingExitStatus::NoNewSongForTooLong => "no new song for too long",
+        RecordingExitStatus::PausedForTooLong => "paused for too long",
+    };
+    println!(
+        "Finished recording ({}). Recorded {} songs.",
+        reason,
+        session.songs.len()
+    );
+    Ok(())
+}
+
+pub fn cut(
+    output_dir: &Path,
+    session: &Path,
+    offset_mode: OffsetMode,
+    cut_scoring: Option<CutScoring>,
+    output_settings: &OutputSettings,
+) -> Result<()> {
+    let session_dir = get_session_dir(output_dir, session)?;
+    let session = RecordingSession::from_parent_dir(&session_dir)?;
+    let session_cut_scoring = session.cut_scoring.unwrap_or_default();
+    let cut_scoring = cut_scoring.unwrap_or(session_cut_scoring);
+    let collection = get_excerpt_collection(session, offset_mode, cut_scoring)?;
+    if collection.session.cut_times.is_some() {
+        if cut_scoring == session_cut_scoring {
+            println!("Using the confirmed cut times instead of the detected ones.");
+        } else {
+            println!(
+                "Ignoring the cut times confirmed with {}.",
+                session_cut_scoring.name()
+            );
+        }
+    }
+    println!(
+        "Using offset: {} ({})",
+        collection
+            .offset_guesses
+            .iter()
+            .map(|offset| format!("{:.3}", offset))
+            .join(", "),
+        cut_scoring.name()
+    );
+    if let Some(ref missing_songs) = collection.missing_songs {
+        println!(
+            "Skipping {} songs which were not recorded fully: {}",
+            missing_songs.songs.len(),
+            missing_songs.error
+        );
+    }
+    if !collection.incomplete_songs.is_empty() {
+        println!(
+            "Skipping {} songs which were not played fully: {}",
+            collection.incomplete_songs.len(),
+            collection.incomplete_songs.iter().join(", ")
+        );
+    }
+    for excerpt in collection.iter_suspicious_excerpts() {
+        println!(
+            "Suspicious cut before {} ({})",
+            excerpt
+                .song_after
+                .as_ref()
+                .map(|song| song.to_string())
+                .unwrap_or_else(|| "end of session".into()),
+            excerpt.confidence
+        );
+    }
+    for info in get_cut_info(&collection, &collection.get_cut_times(), output_settings) {
+        cut_song(&info)?;
+    }
+    Ok(())
+}
+
+pub fn repair(output_dir: &Path, session: &Path) -> Result<()> {
+    let session_dir = get_session_dir(output_dir, session)?;
+    let session = RecordingSession::from_parent_dir(&session_dir)?;
+    let repaired = session.repair_buffer_files()?;
+    if repaired.is_empty() {
+        println!("Nothing to repair.");
+    }
+    for file in repaired {
+        println!("Repaired {:?}", file);
+    }
+    Ok(())
+}
+
+pub fn list_sessions(output_dir: &Path) -> Result<()> {
+    for dir in get_session_dirs(output_dir)? {
+        let name = dir.file_name().unwrap().to_string_lossy();
+        match RecordingSession::from_parent_dir(&dir) {
+            Ok(session) => {
+                let description = session
+                    .songs
+                    .first()
+                    .map(|song| song.to_string())
+                    .unwrap_or_default();
+                println!("{}: {} songs {}", name, session.songs.len(), description)
+            }
+            Err(e) => println!("{}: {}", name, e),
+        }
+    }
+    Ok(())
+}
+
+/// Lists the running MPRIS players along with the services that match
+/// them, as well as the sink inputs, to help with defining new services.
+pub fn discover(user_services: &[Service]) -> Result<()> {
+    let services: Vec<_> = user_services
+        .iter()
+        .cloned()
+        .chain(Service::builtin())
+        .collect();
+    println!("MPRIS players:");
+    for bus_name in get_matching_services(MPRIS_BUS_NAME_PREFIX)? {
+        let matching: Vec<_> = services
+            .iter()
+            .filter(|service| bus_name.starts_with(&service.bus_name))
+            .map(|service| &service.name[..])
+            .collect();
+        if matching.is_empty() {
+            println!("  {}", bus_name);
+        } else {
+            println!("  {} (service: {})", bus_name, matching.join(", "));
+        }
+    }
+    println!("Sink inputs:");
+    for sink_input in recorder::connect().list_sink_inputs()? {
+        let properties: Vec<_> = [
+            "media.name",
+            "application.name",
+            "application.process.binary",
+        ]
+        .iter()
+        .filter_map(|property| {
+            sink_input
+                .properties
+                .get(*property)
+                .map(|value| format!("{} = \"{}\"", property, value))
+        })
+        .collect();
+        println!("  #{}: {}", sink_input.index, properties.join(", "));
+    }
+    Ok(())
+}
+
+/// Allows specifying sessions either by path or by
+/// their name within the output directory.
+fn get_session_dir(output_dir: &Path, session: &Path) -> Result<PathBuf> {
+    if session.is_dir() {
+        Ok(session.into())
+    } else if output_dir.join(session).is_dir() {
+        Ok(output_dir.join(session))
+    } else {
+        Err(anyhow!("Session directory not found: {:?}", session))
+    }
+}
@@ -8,25 +8,88 @@ pub static STRIPUTARY_SINK_DESCRIPTION: &str = "Striputary";
 pub static STRIPUTARY_MONITOR_SINK_NAME: &str = "StriputaryMonitor";
 pub static STRIPUTARY_MONITOR_SINK_DESCRIPTION: &str = "StriputaryMonitor";
 
+pub static PULSE_CLIENT_NAME: &str = "striputary";
+pub static RECORDING_SAMPLE_RATE: u32 = 44100;
+pub static RECORDING_NUM_CHANNELS: u8 = 2;
+// How often the header of the buffer file is updated while recording
+pub static BUFFER_FLUSH_INTERVAL: Duration = Duration::from_secs(1);
+
+// Buffer file of sessions recorded before the buffer was split into segments
 pub static DEFAULT_BUFFER_FILE: &str = "buffer.wav";
+pub static BUFFER_SEGMENT_PREFIX: &str = "buffer_";
+pub static BUFFER_SEGMENT_LENGTH: Duration = Duration::from_secs(30 * 60);
 pub static DEFAULT_SESSION_FILE: &str = "session.yaml";
 pub static DEFAULT_MUSIC_DIR: &str = "music";
+pub static DEFAULT_COVER_DIR: &str = "covers";
 
-pub static DEFAULT_SERVICE: &str = "spotify";
+pub static DEFAULT_SERVICE: &str = "spotify_native";
+pub static MPRIS_BUS_NAME_PREFIX: &str = "org.mpris.MediaPlayer2.";
 // This should be more than 3-4 seconds at least
 pub static TIME_BEFORE_SESSION_START: Duration = Duration::from_secs(5);
 pub static WAIT_TIME_BEFORE_FIRST_SONG: Duration = Duration::from_secs(1);
 pub static TIME_AFTER_SESSION_END: Duration = Duration::from_secs(10);
 
 pub static TIME_WITHOUT_DBUS_SIGNAL_BEFORE_STOPPING: Duration = Duration::from_secs(10);
+// The session ends if the playback is paused for longer than this
+pub static MAX_PAUSE_DURATION: Duration = Duration::from_secs(30 * 60);
 pub static TIME_BETWEEN_SUBSEQUENT_DBUS_COMMANDS: Duration = Duration::from_secs(1);
+pub static DBUS_METHOD_CALL_TIMEOUT_MS: i32 = 2000;
+// In seconds. Differences between the song lengths and the times of the
+// MPRIS signals below this are attributed to imprecise signal timing.
+pub static PLAYBACK_TIME_TOLERANCE: f64 = 3.0;
+// Seeking to a position (in seconds) below this counts as starting the song over
+pub static MAX_RESTART_POSITION: f64 = 1.0;
+// In seconds. How far from the estimated beginning and end of a pause
+// to look for the silence that was recorded while the player was paused
+pub static PAUSE_EDGE_SEARCH_RANGE: f64 = 1.0;
+
+pub static COVER_ART_DOWNLOAD_TIMEOUT: Duration = Duration::from_secs(10);
+// In bytes
+pub static MAX_COVER_ART_SIZE: u64 = 20_000_000;
 
+// The highest bit depth supported by the flac encoder. Deeper
+// and float samples are reduced to it.
+pub static MAX_FLAC_BITS_PER_SAMPLE: u16 = 24;
+// Default bitrate for lossy output formats
 pub static BITRATE: i64 = 192000;
 pub static MIN_OFFSET: f64 = -3.;
 pub static MAX_OFFSET: f64 = 3.;
 pub static READ_BUFFER: f64 = 0.5;
 pub static NUM_OFFSETS_TO_TRY: i64 = 1000;
-pub static NUM_SAMPLES_PER_AVERAGE_VOLUME: usize = 2000;
+pub static NUM_FRAMES_PER_AVERAGE_VOLUME: usize = 1000;
+// Used when refining the offset for every cut individually
+pub static LOCAL_OFFSET_SEARCH_RANGE: f64 = 0.3;
+pub static NUM_LOCAL_OFFSETS_TO_TRY: i64 = 200;
+// Penalty (per second, relative to the scale of the scores) for
+// deviating from the global offset
+pub static LOCAL_OFFSET_DEVIATION_PENALTY: f64 = 0.25;
+// The resolution (in seconds) of the features used by the cut scoring strategies
+pub static CUT_SCORING_HOP_TIME: f64 = 0.01;
+// In samples, has to be a power of two
+pub static SPECTRAL_FLUX_FRAME_SIZE: usize = 2048;
+pub static SPECTRAL_FLUX_COMPRESSION: f64 = 100.0;
+// The width (in seconds) over which the onsets are smeared out
+pub static SPECTRAL_FLUX_SMOOTHING: f64 = 0.03;
+// In seconds. Positions can only be scored if there are at least
+// window + max lag seconds of audio before them.
+pub static ENERGY_CORRELATION_WINDOW: f64 = 0.5;
+pub static ENERGY_CORRELATION_MAX_LAG: f64 = 0.5;
+// Positions whose volume is below this fraction of the average
+// volume of the excerpt count as quiet
+pub static QUIET_RELATIVE_VOLUME: f64 = 0.1;
+// A cut is flagged as suspicious if the volume at the cut is above
+// this fraction of the average volume of the excerpt ...
+pub static SUSPICIOUS_RELATIVE_VOLUME: f64 = 0.3;
+// ... or if it is further than this (in seconds) from the nearest quiet position
+pub static SUSPICIOUS_DISTANCE_TO_QUIET: f64 = 0.1;
+pub static CONFIDENCE_SEARCH_STEP: f64 = 0.01;
+// In seconds. How far a cut may be moved when snapping
+// it to the nearest zero crossing or quietest position.
+pub static SNAP_TO_ZERO_CROSSING_MAX_DISTANCE: f64 = 0.01;
+pub static SNAP_TO_QUIETEST_TIME_MAX_DISTANCE: f64 = 0.1;
+pub static QUIETEST_TIME_SEARCH_STEP: f64 = 0.001;
+// The number of edits of the cut positions which can be undone
+pub static MAX_CUT_HISTORY_LENGTH: usize = 100;
 
 pub static NUM_PLOT_DATA_POINTS: i64 = 500;
 
@@ -8,13 +8,28 @@ use anyhow::Result;
 use serde::Deserialize;
 
 use crate::config;
+use crate::offset_mode::OffsetMode;
+use crate::output_format::OutputFormat;
+use crate::path_template::Sanitization;
+use crate::pause_mode::PauseMode;
 use crate::service_config::Service;
 
 #[derive(Deserialize, Debug)]
 pub struct ConfigFile {
     pub output_dir: PathBuf,
-    pub service: Option<Service>,
+    pub service: Option<String>,
+    /// Additional user-defined services
+    #[serde(default)]
+    pub services: Vec<Service>,
     pub monitor: Option<bool>,
+    pub pause_mode: Option<PauseMode>,
+    pub offset_mode: Option<OffsetMode>,
+    pub output_format: Option<OutputFormat>,
+    pub bitrate: Option<i64>,
+    pub vbr_quality: Option<f64>,
+    pub flac_compression_level: Option<u32>,
+    pub path_template: Option<String>,
+    pub sanitization: Option<Sanitization>,
 }
 
 impl ConfigFile {
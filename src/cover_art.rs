@@ -0,0 +1,182 @@
+//! Cover art is downloaded while recording and cached per album in
+//! the session directory, so that cutting does not need the network.
+
+use std::fs::create_dir_all;
+use std::fs::{self};
+use std::io::Read;
+use std::path::Path;
+use std::path::PathBuf;
+use std::sync::mpsc::channel;
+use std::sync::mpsc::Sender;
+use std::thread;
+use std::thread::JoinHandle;
+
+use anyhow::anyhow;
+use anyhow::Context;
+use anyhow::Result;
+use imagesize::ImageType;
+
+use crate::config::COVER_ART_DOWNLOAD_TIMEOUT;
+use crate::config::MAX_COVER_ART_SIZE;
+use crate::path_template::Sanitization;
+use crate::song::Song;
+
+/// The picture type for the front cover, as defined by ID3v2 and FLAC.
+static FRONT_COVER_PICTURE_TYPE: u32 = 3;
+
+pub struct CoverArt {
+    data: Vec<u8>,
+    mime_type: &'static str,
+    width: u32,
+    height: u32,
+}
+
+impl CoverArt {
+    pub fn from_file(path: &Path) -> Result<Self> {
+        let data = fs::read(path).context("Failed to read cover art")?;
+        let mime_type = match imagesize::image_type(&data) {
+            Ok(ImageType::Jpeg) => "image/jpeg",
+            Ok(ImageType::Png) => "image/png",
+            Ok(ImageType::Gif) => "image/gif",
+            _ => return Err(anyhow!("Unsupported image format in {:?}", path)),
+        };
+        let size = imagesize::blob_size(&data)
+            .map_err(|e| anyhow!("Failed to read size of cover art: {}", e))?;
+        Ok(Self {
+            data,
+            mime_type,
+            width: size.width as u32,
+            height: size.height as u32,
+        })
+    }
+
+    /// The contents of a FLAC picture metadata block. This is also
+    /// the format used for cover art in ogg files (base64 encoded).
+    pub fn to_flac_picture(&self) -> Vec<u8> {
+        let mut block = vec![];
+        block.extend(FRONT_COVER_PICTURE_TYPE.to_be_bytes());
+        block.extend((self.mime_type.len() as u32).to_be_bytes());
+        block.extend(self.mime_type.as_bytes());
+        // Empty description
+        block.extend(0u32.to_be_bytes());
+        block.extend(self.width.to_be_bytes());
+        block.extend(self.height.to_be_bytes());
+        // Color depth and number of colors. These are informative only,
+        // so we do not bother decoding the image to find out.
+        block.extend(24u32.to_be_bytes());
+        block.extend(0u32.to_be_bytes());
+        block.extend((self.data.len() as u32).to_be_bytes());
+        block.extend(&self.data);
+        block
+    }
+}
+
+/// The file in which the cover art of the song's album is cached.
+/// Returns None if the song does not belong to an album.
+fn get_cover_file(cover_dir: &Path, song: &Song) -> Option<PathBuf> {
+    let album = song.album.as_ref()?;
+    let name = match song.album_artist() {
+        Some(album_artist) => format!("{} - {}", album_artist, album),
+        None => album.clone(),
+    };
+    Some(cover_dir.join(Sanitization::Filesystem.sanitize(&name)))
+}
+
+/// Returns the cached cover art file for the song, if there is one.
+pub fn get_cached_cover_file(cover_dir: &Path, song: &Song) -> Option<PathBuf> {
+    get_cover_file(cover_dir, song).filter(|file| file.exists())
+}
+
+/// Downloads the cover art of the song into the cover directory,
+/// unless it has already been cached for the song's album.
+pub fn cache_cover_art(cover_dir: &Path, song: &Song) -> Result<()> {
+    let (file, url) = match (get_cover_file(cover_dir, song), &song.art_url) {
+        (Some(file), Some(url)) => (file, url),
+        _ => return Ok(()),
+    };
+    if file.exists() {
+        return Ok(());
+    }
+    let data = fetch(url).context(format!("Failed to get cover art from {}", url))?;
+    create_dir_all(cover_dir).context("Failed to create cover directory")?;
+    fs::write(&file, data).context("Failed to write cover art")
+}
+
+/// Downloads cover art on a separate thread, so that slow
+/// downloads do not hold up the handling of dbus signals.
+pub struct CoverArtDownloader {
+    sender: Sender<Song>,
+    thread: JoinHandle<()>,
+}
+
+impl CoverArtDownloader {
+    pub fn new(cover_dir: PathBuf) -> Self {
+        let (sender, receiver) = channel::<Song>();
+        let thread = thread::spawn(move || {
+            for song in receiver {
+                if let Err(e) = cache_cover_art(&cover_dir, &song) {
+                    println!("{:?}", e);
+                }
+            }
+        });
+        Self { sender, thread }
+    }
+
+    pub fn download(&self, song: &Song) {
+        // Sending only fails if the thread panicked, in which case
+        // the recording should go on without cover art.
+        if let Err(e) = self.sender.send(song.clone()) {
+            println!("Failed to queue cover art download: {}", e);
+        }
+    }
+
+    /// Waits until the cover art of all songs has been downloaded.
+    pub fn finish(self) {
+        drop(self.sender);
+        if self.thread.join().is_err() {
+            println!("Cover art download thread panicked");
+        }
+    }
+}
+
+fn fetch(url: &str) -> Result<Vec<u8>> {
+    if let Some(path) = url.strip_prefix("file://") {
+        Ok(fs::read(percent_decode(path)?)?)
+    } else if url.starts_with("http://") || url.starts_with("https://") {
+        let response = ureq::AgentBuilder::new()
+            .timeout(COVER_ART_DOWNLOAD_TIMEOUT)
+            .build()
+            .get(url)
+            .call()?;
+        let mut data = vec![];
+        // Read one more byte than allowed to find out whether the image is too large.
+        response
+            .into_reader()
+            .take(MAX_COVER_ART_SIZE + 1)
+            .read_to_end(&mut data)?;
+        if data.len() as u64 > MAX_COVER_ART_SIZE {
+            return Err(anyhow!(
+                "Cover art is larger than {} bytes",
+                MAX_COVER_ART_SIZE
+            ));
+        }
+        Ok(data)
+    } else {
+        Err(anyhow!("Unsupported URL scheme"))
+    }
+}
+
+fn percent_decode(s: &str) -> Result<String> {
+    let mut bytes = vec![];
+    let mut iter = s.bytes();
+    while let Some(byte) = iter.next() {
+        if byte == b'%' {
+            let hex: Vec<u8> = iter.by_ref().take(2).collect();
+            let hex = std::str::from_utf8(&hex)?;
+            bytes.push(u8::from_str_radix(hex, 16)?);
+        } else {
+            bytes.push(byte);
+        }
+    }
+    Ok(String::from_utf8(bytes)?)
+}
@@ -1,32 +1,62 @@
-use std::fmt::Display;
 use std::fs::create_dir_all;
+use std::fs::{self};
+use std::io::ErrorKind;
+use std::ops::Range;
 use std::path::Path;
 use std::path::PathBuf;
 use std::process::Command;
 
+use anyhow::anyhow;
 use anyhow::Context;
 use anyhow::Result;
+use base64::Engine;
 
 use crate::audio_excerpt::AudioExcerpt;
 use crate::audio_time::AudioTime;
+use crate::config::LOCAL_OFFSET_DEVIATION_PENALTY;
+use crate::config::LOCAL_OFFSET_SEARCH_RANGE;
 use crate::config::MAX_OFFSET;
 use crate::config::MIN_OFFSET;
+use crate::config::NUM_LOCAL_OFFSETS_TO_TRY;
 use crate::config::NUM_OFFSETS_TO_TRY;
+use crate::config::PAUSE_EDGE_SEARCH_RANGE;
 use crate::config::READ_BUFFER;
-use crate::config::{self};
+use crate::cover_art::get_cached_cover_file;
+use crate::cover_art::CoverArt;
+use crate::cut_confidence::CutConfidence;
+use crate::cut_history::CutHistory;
+use crate::cut_scoring::CutScorer;
+use crate::cut_scoring::CutScoring;
+use crate::encode::write_flac;
+use crate::encode::write_wav;
+use crate::errors::ExcerptError;
 use crate::excerpt_collection::ExcerptCollection;
+use crate::excerpt_collection::MissingSongs;
 use crate::excerpt_collection::NamedExcerpt;
+use crate::offset_mode::OffsetMode;
+use crate::output_format::MetadataTag;
+use crate::output_format::OutputFormat;
+use crate::output_format::OutputSettings;
 use crate::recording_session::RecordingSession;
 use crate::song::Song;
+use crate::timeline::get_cut_points;
+use crate::timeline::get_segments;
+use crate::timeline::get_song_playbacks;
+use crate::timeline::CutPoint;
+use crate::timeline::Pause;
 use crate::wav::extract_audio;
 
 pub struct CutInfo {
     pub song: Song,
-    buffer_file: PathBuf,
+    buffer_files: Vec<PathBuf>,
     music_dir: PathBuf,
     start_time: AudioTime,
     end_time: AudioTime,
+    /// The estimated positions of the pauses within the song in the buffer.
+    pauses: Vec<Pause>,
     num_in_recording: usize,
+    output_settings: OutputSettings,
+    cover_file: Option<PathBuf>,
 }
 
 impl CutInfo {
@@ -35,181 +65,566 @@ impl CutInfo {
         song: Song,
         start_time: AudioTime,
         end_time: AudioTime,
+        pauses: Vec<Pause>,
         num_in_recording: usize,
+        output_settings: OutputSettings,
     ) -> Self {
-        let buffer_file = session.get_buffer_file();
+        let buffer_files = session.get_buffer_files();
         let music_dir = session.get_music_dir();
+        let cover_file = get_cached_cover_file(&session.get_cover_dir(), &song);
         CutInfo {
             song,
-            buffer_file,
+            buffer_files,
             music_dir,
             start_time,
             end_time,
+            pauses,
             num_in_recording,
+            output_settings,
+            cover_file,
         }
     }
 }
 
-fn get_excerpt(buffer_file_name: &Path, cut_time: f64) -> Option<AudioExcerpt> {
+fn get_excerpt(buffer_files: &[PathBuf], cut_time: f64) -> Result<AudioExcerpt, ExcerptError> {
     let listen_start_time = cut_time + MIN_OFFSET - READ_BUFFER;
     let listen_end_time = cut_time + MAX_OFFSET + READ_BUFFER;
-    extract_audio(buffer_file_name, listen_start_time, listen_end_time).ok()
+    extract_audio(buffer_files, listen_start_time, listen_end_time)
 }
 
-fn get_cut_timestamps_from_song_lengths(
-    songs: &[Song],
-    estimated_time_first_song: f64,
-) -> Vec<f64> {
-    songs
-        .iter()
-        .scan(estimated_time_first_song, |acc, song| {
-            let result = Some(*acc);
-            *acc += song.length;
-            result
-        })
-        .collect()
-}
-
-fn determine_cut_offset(audio_excerpts: &[AudioExcerpt], cut_timestamps: &[f64]) -> f64 {
-    // We can assume that some of the songs begin or end with silence.
-    // If that is the case then the offset of the cuts should be chosen by finding an offset that
-    // puts as many of the cuts at positions where the recording is silent. In other words, the offset is given by
-    // the local minimum of the convolution of the volume with a sum of dirac deltas at every cut position.
+fn determine_cut_offset(
+    scorer: &dyn CutScorer,
+    cut_timestamps: &[f64],
+    segment: Range<usize>,
+) -> f64 {
+    // Within a segment, the recording is offset from the song lengths by the same amount at every
+    // cut. This offset is chosen such that as many of the cuts as possible end up at positions
+    // which look like the transition between two songs, i.e. it minimizes the sum of the scores
+    // at all cut positions.
     let mut min: Option<(f64, f64)> = None;
     for i in 0..NUM_OFFSETS_TO_TRY {
         let offset =
             (i as f64) / (NUM_OFFSETS_TO_TRY as f64) * (MAX_OFFSET - MIN_OFFSET) + MIN_OFFSET;
-        let total_volume: f64 = cut_timestamps
-            .iter()
-            .zip(audio_excerpts.iter())
-            .map(|(cut_time, audio_excerpt)| audio_excerpt.get_volume_at(cut_time + offset))
+        let total_score: f64 = segment
+            .clone()
+            .map(|num| scorer.get_score(num, cut_timestamps[num] + offset))
             .sum();
-        if let Some((min_volume, _)) = min {
-            if total_volume < min_volume {
-                min = Some((total_volume, offset));
+        if let Some((min_score, _)) = min {
+            if total_score < min_score {
+                min = Some((total_score, offset));
             }
         } else {
-            min = Some((total_volume, offset));
+            min = Some((total_score, offset));
         };
     }
-    let cut_quality_estimate = min.unwrap().0 / (audio_excerpts.len() as f64);
-    println!("Av. volume at cuts: {:.3}", cut_quality_estimate);
     min.unwrap().1
 }
 
-pub fn get_excerpt_collection(session: RecordingSession) -> ExcerptCollection {
-    let (excerpts, songs) = get_all_valid_excerpts_and_songs(&session);
-    let timestamps =
-        get_cut_timestamps_from_song_lengths(&songs, session.estimated_time_first_song);
-    let offset_guess = determine_cut_offset(&excerpts, &timestamps);
+/// Refines the offset of its segment for every cut individually by
+/// searching for the best scored position in a small range around
+/// it. Deviations from the segment offset are penalized relative to
+/// the scale of the scores, so that cuts without a clear minimum
+/// stay close to the global guess with every scorer.
+fn determine_local_cut_offsets(
+    audio_excerpts: &[AudioExcerpt],
+    scorer: &dyn CutScorer,
+    global_offsets: &[f64],
+) -> Vec<f64> {
+    audio_excerpts
+        .iter()
+        .zip(global_offsets)
+        .enumerate()
+        .map(|(num, (audio_excerpt, global_offset))| {
+            let global_offset = *global_offset;
+            let min_offset = (global_offset - LOCAL_OFFSET_SEARCH_RANGE).max(MIN_OFFSET);
+            let max_offset = (global_offset + LOCAL_OFFSET_SEARCH_RANGE).min(MAX_OFFSET);
+            let penalty_per_second = LOCAL_OFFSET_DEVIATION_PENALTY * scorer.get_score_scale(num);
+            (0..=NUM_LOCAL_OFFSETS_TO_TRY)
+                .map(|i| {
+                    (i as f64) / (NUM_LOCAL_OFFSETS_TO_TRY as f64) * (max_offset - min_offset)
+                        + min_offset
+                })
+                .map(|offset| {
+                    let penalty = penalty_per_second * (offset - global_offset).abs();
+                    let time = audio_excerpt.get_absolute_time_from_time_offset(offset);
+                    (scorer.get_score(num, time.time) + penalty, offset)
+                })
+                .filter(|(score, _)| score.is_finite())
+                .min_by(|(score1, _), (score2, _)| score1.total_cmp(score2))
+                .map(|(_, offset)| offset)
+                .unwrap_or(global_offset)
+        })
+        .collect()
+}
+
+pub fn get_excerpt_collection(
+    mut session: RecordingSession,
+    offset_mode: OffsetMode,
+    cut_scoring: CutScoring,
+) -> Result<ExcerptCollection, ExcerptError> {
+    let playbacks = get_song_playbacks(&session);
+    let incomplete_songs = session
+        .songs
+        .iter()
+        .zip(playbacks.iter())
+        .filter(|(_, playback)| !playback.complete)
+        .map(|(song, _)| song.clone())
+        .collect();
+    let ValidExcerptsAndCutPoints {
+        excerpts,
+        cut_points,
+        missing_songs,
+    } = get_all_valid_excerpts_and_cut_points(&session, &get_cut_points(&playbacks))?;
+    let timestamps: Vec<f64> = cut_points.iter().map(|cut_point| cut_point.time).collect();
+    let segments = get_segments(&cut_points, &playbacks, &session.segment_starts);
+    let (offset_guesses, mut offsets) = {
+        let scorer = cut_scoring.get_scorer(&excerpts);
+        let offset_guesses: Vec<f64> = segments
+            .iter()
+            .map(|segment| determine_cut_offset(scorer.as_ref(), &timestamps, segment.clone()))
+            .collect();
+        let global_offsets: Vec<f64> = segments
+            .iter()
+            .zip(offset_guesses.iter())
+            .flat_map(|(segment, offset_guess)| segment.clone().map(move |_| *offset_guess))
+            .collect();
+        let offsets = match offset_mode {
+            OffsetMode::Global => global_offsets,
+            OffsetMode::PerCut => {
+                determine_local_cut_offsets(&excerpts, scorer.as_ref(), &global_offsets)
+            }
+        };
+        (offset_guesses, offsets)
+    };
+    let guessed_offsets = offsets.clone();
+    discard_outdated_cut_times(&mut session, excerpts.len());
+    let confirmed_cut_times = session
+        .cut_times
+        .as_ref()
+        .filter(|_| session.cut_scoring.unwrap_or_default() == cut_scoring);
+    if let Some(cut_times) = confirmed_cut_times {
+        // Cut times confirmed by the user take precedence over the
+        // offsets computed with the strategy they were confirmed with.
+        for ((offset, excerpt), cut_time) in offsets.iter_mut().zip(excerpts.iter()).zip(cut_times)
+        {
+            *offset = excerpt.get_time_offset_from_absolute_time(*cut_time);
+        }
+    }
+    let get_song = |num: Option<usize>| num.map(|num| session.songs[num].clone());
     let excerpts: Vec<NamedExcerpt> = excerpts
         .into_iter()
+        .zip(offsets)
+        .zip(guessed_offsets)
+        .zip(cut_points)
         .enumerate()
-        .map(|(num, excerpt)| {
-            let song_before = if num == 0 { None } else { songs.get(num) };
+        .map(|(num, (((excerpt, offset), offset_guess), cut_point))| {
+            let cut_time = excerpt.get_absolute_time_from_time_offset(offset);
+            let confidence = CutConfidence::new(&excerpt, cut_time.time);
             NamedExcerpt {
                 excerpt,
-                song_before: song_before.cloned(),
-                song_after: songs.get(num).cloned(),
+                song_before: get_song(cut_point.song_before),
+                song_after: get_song(cut_point.song_after),
                 num,
+                offset,
+                offset_guess,
+                confidence,
+                pauses_after: cut_point
+                    .song_after
+                    .map(|num| playbacks[num].pauses.clone())
+                    .unwrap_or_default(),
             }
         })
         .collect();
-    ExcerptCollection {
+    Ok(ExcerptCollection {
         session,
         excerpts,
-        offset_guess,
-    }
+        offset_guesses,
+        cut_scoring,
+        missing_songs,
+        incomplete_songs,
+    })
+}
+
+/// The confirmed cut times are stored by position, so they (and the
+/// edits which led to them) only apply as long as their number
+/// matches the number of cuts.
+fn discard_outdated_cut_times(session: &mut RecordingSession, num_cuts: usize) {
+    let num_cut_times = match session.cut_times {
+        Some(ref cut_times) if cut_times.len() != num_cuts => cut_times.len(),
+        _ => return,
+    };
+    println!(
+        "Discarding {} confirmed cut times, since the session has {} cuts.",
+        num_cut_times, num_cuts
+    );
+    session.cut_times = None;
+    session.individually_moved_cuts.clear();
+    session.cut_history = CutHistory::default();
+}
+
+struct ValidExcerptsAndCutPoints {
+    excerpts: Vec<AudioExcerpt>,
+    cut_points: Vec<CutPoint>,
+    missing_songs: Option<MissingSongs>,
 }
 
-fn get_all_valid_excerpts_and_songs(session: &RecordingSession) -> (Vec<AudioExcerpt>, Vec<Song>) {
+/// Reads the excerpts around every cut. If the recording ends before
+/// the session does, the songs which were not recorded fully are
+/// returned separately.
+fn get_all_valid_excerpts_and_cut_points(
+    session: &RecordingSession,
+    cut_points: &[CutPoint],
+) -> Result<ValidExcerptsAndCutPoints, ExcerptError> {
+    let buffer_files = session.get_buffer_files();
     let mut audio_excerpts = Vec::new();
-    let mut valid_songs = Vec::new();
-    let mut cut_time = session.estimated_time_first_song;
-    for song in session.songs.iter() {
-        let audio_excerpt = get_excerpt(&session.get_buffer_file(), cut_time);
-        if let Some(excerpt) = audio_excerpt {
-            audio_excerpts.push(excerpt);
-            valid_songs.push(song.clone());
-        } else {
-            break;
+    for cut_point in cut_points.iter() {
+        match get_excerpt(&buffer_files, cut_point.time) {
+            Ok(excerpt) => audio_excerpts.push(excerpt),
+            Err(error @ ExcerptError::PastEndOfBuffer { .. }) => {
+                let (valid_cut_points, invalid_cut_points) =
+                    cut_points.split_at(audio_excerpts.len());
+                // Every song needs the excerpts at its start and its end.
+                let missing_songs = invalid_cut_points
+                    .iter()
+                    .filter_map(|cut_point| cut_point.song_before)
+                    .map(|num| session.songs[num].clone())
+                    .collect();
+                let mut valid_cut_points = valid_cut_points.to_vec();
+                if let Some(cut_point) = valid_cut_points.last_mut() {
+                    cut_point.song_after = None;
+                }
+                if let Some(cut_point) = valid_cut_points.last() {
+                    if cut_point.song_before.is_none() {
+                        // Neither begins nor ends a song anymore.
+                        valid_cut_points.pop();
+                        audio_excerpts.pop();
+                    }
+                }
+                return Ok(ValidExcerptsAndCutPoints {
+                    excerpts: audio_excerpts,
+                    cut_points: valid_cut_points,
+                    missing_songs: Some(MissingSongs {
+                        songs: missing_songs,
+                        error,
+                    }),
+                });
+            }
+            Err(error) => return Err(error),
         }
-        cut_time += song.length;
-    }
-    let audio_excerpt_after_last_song = get_excerpt(&session.get_buffer_file(), cut_time);
-    if let Some(audio_excerpt_after_last_song) = audio_excerpt_after_last_song {
-        audio_excerpts.push(audio_excerpt_after_last_song);
     }
-    (audio_excerpts, valid_songs)
+    Ok(ValidExcerptsAndCutPoints {
+        excerpts: audio_excerpts,
+        cut_points: cut_points.to_vec(),
+        missing_songs: None,
+    })
+}
+
+/// Returns the information required to cut every song in the
+/// collection, given the final cut time for each excerpt.
+pub fn get_cut_info(
+    collection: &ExcerptCollection,
+    cut_times: &[AudioTime],
+    output_settings: &OutputSettings,
+) -> Vec<CutInfo> {
+    let excerpts: Vec<_> = collection.iter_excerpts().zip(cut_times).collect();
+    excerpts
+        .iter()
+        .zip(excerpts.iter().skip(1))
+        // The cut after the beginning of a song is always its end.
+        .filter_map(|(start, end)| Some((start.0.song_after.as_ref()?, start, end)))
+        .enumerate()
+        .map(
+            |(i, (song, (start_excerpt, start_time), (end_excerpt, end_time)))| {
+                // The MPRIS signals of the pauses are offset from
+                // the recording just like those of the cuts.
+                let start_offset = start_excerpt
+                    .excerpt
+                    .get_time_offset_from_absolute_time(start_time.time);
+                let end_offset = end_excerpt
+                    .excerpt
+                    .get_time_offset_from_absolute_time(end_time.time);
+                let pauses = start_excerpt
+                    .pauses_after
+                    .iter()
+                    .map(|pause| Pause {
+                        start: pause.start + start_offset,
+                        end: pause.end + end_offset,
+                    })
+                    .collect();
+                CutInfo::new(
+                    &collection.session,
+                    song.clone(),
+                    **start_time,
+                    **end_time,
+                    pauses,
+                    i,
+                    output_settings.clone(),
+                )
+            },
+        )
+        .collect()
+}
+
+/// Returns the tags of the song. Tags with multiple values, such as
+/// the artists, appear once per value.
+fn get_tags(song: &Song) -> Vec<(MetadataTag, String)> {
+    let single_values = vec![
+        (MetadataTag::Title, song.title.clone()),
+        (MetadataTag::Album, song.album.clone()),
+        (
+            MetadataTag::TrackNumber,
+            song.track_number
+                .map(|track_number| track_number.to_string()),
+        ),
+        (
+            MetadataTag::DiscNumber,
+            song.disc_number.map(|disc_number| disc_number.to_string()),
+        ),
+        (MetadataTag::Date, song.content_created.clone()),
+        (MetadataTag::TrackId, song.track_id.clone()),
+    ];
+    let album_artists = if song.album_artists.is_empty() {
+        song.all_artists()
+    } else {
+        song.album_artists.clone()
+    };
+    let multiple_values = song
+        .all_artists()
+        .into_iter()
+        .map(|artist| (MetadataTag::Artist, artist))
+        .chain(
+            album_artists
+                .into_iter()
+                .map(|artist| (MetadataTag::AlbumArtist, artist)),
+        )
+        .chain(
+            song.genres
+                .iter()
+                .map(|genre| (MetadataTag::Genre, genre.clone())),
+        );
+    single_values
+        .into_iter()
+        .filter_map(|(tag, value)| value.map(|value| (tag, value)))
+        .chain(multiple_values)
+        .collect()
 }
 
-fn add_metadata_arg_if_present<T: Display>(
-    command: &mut Command,
-    get_str: fn(&T) -> String,
-    val: Option<&T>,
-) {
-    if let Some(val) = val {
-        command.arg("-metadata").arg(get_str(&val));
+/// Joins multiple values of the same tag into a single value, for
+/// formats which can only hold one value per tag.
+fn join_multiple_values(tags: &[(MetadataTag, String)]) -> Vec<(MetadataTag, String)> {
+    let mut joined: Vec<(MetadataTag, String)> = vec![];
+    for (tag, value) in tags {
+        match joined.iter_mut().find(|(other, _)| other == tag) {
+            Some((_, joined_value)) => {
+                joined_value.push_str("; ");
+                joined_value.push_str(value);
+            }
+            None => joined.push((*tag, value.clone())),
+        }
     }
+    joined
 }
 
 pub fn cut_song(info: &CutInfo) -> Result<()> {
-    let difference = info.end_time.time - info.start_time.time;
-    let target_file = info
-        .song
-        .get_target_file(&info.music_dir, info.num_in_recording);
+    let target_file = info.output_settings.path_template.get_target_file(
+        &info.music_dir,
+        &info.song,
+        info.num_in_recording,
+        info.output_settings.extension(),
+        info.output_settings.sanitization,
+    );
     create_dir_all(target_file.parent().unwrap())
         .context("Failed to create subfolders of target file")?;
     println!(
         "Cutting song: {:.2}+{:.2}: {} to {}",
         info.start_time.time,
-        difference,
+        info.end_time.time - info.start_time.time,
         info.song,
         target_file.to_str().unwrap()
     );
+    let tags = get_tags(&info.song);
+    let cover_art = info
+        .cover_file
+        .as_ref()
+        .and_then(|file| match CoverArt::from_file(file) {
+            Ok(cover_art) => Some(cover_art),
+            Err(e) => {
+                println!("{:?}. Cutting without cover art.", e);
+                None
+            }
+        });
+    extract_song_audio(info)
+        .map_err(anyhow::Error::from)
+        .and_then(|excerpt| {
+            if info.output_settings.format.can_encode_natively() {
+                cut_song_native(info, &excerpt, &target_file, &tags, cover_art.as_ref())
+            } else {
+                cut_song_ffmpeg(info, &excerpt, &target_file, &tags, cover_art.as_ref())
+            }
+        })
+        .context(format!(
+            "Failed to cut song: {:?} {:?} {:?} ({:?}+{:?})",
+            &info.song.title,
+            &info.song.album,
+            &info.song.artist,
+            info.start_time.time,
+            info.end_time.time - info.start_time.time,
+        ))
+}
+
+/// Reads the audio of the song from the buffer, leaving
+/// out the silence recorded while the song was paused.
+fn extract_song_audio(info: &CutInfo) -> Result<AudioExcerpt, ExcerptError> {
+    let mut boundaries = vec![info.start_time.time];
+    for pause in info.pauses.iter() {
+        let pause = find_recorded_pause(&info.buffer_files, pause)?;
+        boundaries.push(pause.start);
+        boundaries.push(pause.end);
+    }
+    boundaries.push(info.end_time.time);
+    let mut parts = boundaries
+        .chunks(2)
+        .map(|part| extract_audio(&info.buffer_files, part[0], part[1]));
+    let mut excerpt = parts.next().unwrap()?;
+    for part in parts {
+        excerpt.append(part?);
+    }
+    Ok(excerpt)
+}
+
+/// The player sends nothing while it is paused, so the recording
+/// contains digital silence. Its edges are more precise than
+/// the estimate of the pause from the MPRIS signals.
+fn find_recorded_pause(buffer_files: &[PathBuf], estimate: &Pause) -> Result<Pause, ExcerptError> {
+    let around_start = extract_audio(
+        buffer_files,
+        estimate.start - PAUSE_EDGE_SEARCH_RANGE,
+        estimate.start + PAUSE_EDGE_SEARCH_RANGE,
+    )?;
+    let around_end = extract_audio(
+        buffer_files,
+        estimate.end - PAUSE_EDGE_SEARCH_RANGE,
+        estimate.end + PAUSE_EDGE_SEARCH_RANGE,
+    )?;
+    let start = around_start
+        .get_start_of_final_silence()
+        .unwrap_or(estimate.start);
+    let end = around_end
+        .get_end_of_initial_silence()
+        .unwrap_or(estimate.end)
+        .max(start);
+    Ok(Pause { start, end })
+}
+
+/// Cuts the song by copying the exact range of frames from the
+/// buffer, without relying on any external program.
+fn cut_song_native(
+    info: &CutInfo,
+    excerpt: &AudioExcerpt,
+    target_file: &Path,
+    tags: &[(MetadataTag, String)],
+    cover_art: Option<&CoverArt>,
+) -> Result<()> {
+    match info.output_settings.format {
+        OutputFormat::Wav => write_wav(
+            target_file,
+            excerpt.spec,
+            &excerpt.samples,
+            &join_multiple_values(tags),
+        ),
+        OutputFormat::Flac => write_flac(
+            target_file,
+            excerpt.spec,
+            &excerpt.samples,
+            tags,
+            info.output_settings.flac_compression_level,
+            cover_art,
+        ),
+        format => Err(anyhow!("No native encoder for {:?}", format)),
+    }
+}
+
+/// A file which is removed once it is dropped, so that
+/// it is cleaned up no matter how cutting ends.
+struct TemporaryFile(PathBuf);
+
+impl Drop for TemporaryFile {
+    fn drop(&mut self) {
+        if let Err(e) = fs::remove_file(&self.0) {
+            if e.kind() != ErrorKind::NotFound {
+                println!("Failed to remove temporary file {:?}: {}", self.0, e);
+            }
+        }
+    }
+}
+
+fn cut_song_ffmpeg(
+    info: &CutInfo,
+    excerpt: &AudioExcerpt,
+    target_file: &Path,
+    tags: &[(MetadataTag, String)],
+    cover_art: Option<&CoverArt>,
+) -> Result<()> {
+    let format = info.output_settings.format;
+    // The song may span multiple buffer segments, so we write the
+    // excerpt into a file of its own and let ffmpeg encode that.
+    let excerpt_file = TemporaryFile(target_file.with_extension("excerpt.wav"));
+    write_wav(&excerpt_file.0, excerpt.spec, &excerpt.samples, &[])?;
     let mut command = Command::new("ffmpeg");
-    command
-        .arg("-ss")
-        .arg(format!("{}", info.start_time.time))
-        .arg("-t")
-        .arg(format!("{}", difference))
-        .arg("-i")
-        .arg(&info.buffer_file.to_str().unwrap())
-        .arg("-c:a")
-        .arg("libopus")
-        .arg("-b:a")
-        .arg(format!("{}", config::BITRATE));
-    add_metadata_arg_if_present(
-        &mut command,
-        |title| format!("title={}", title),
-        info.song.title.as_ref(),
-    );
-    add_metadata_arg_if_present(
-        &mut command,
-        |album| format!("album={}", album),
-        info.song.album.as_ref(),
-    );
-    add_metadata_arg_if_present(
-        &mut command,
-        |artist| format!("artist={}", artist),
-        info.song.artist.as_ref(),
-    );
-    add_metadata_arg_if_present(
-        &mut command,
-        |artist| format!("albumartist={}", artist),
-        info.song.artist.as_ref(),
-    );
-    add_metadata_arg_if_present(
-        &mut command,
-        |track_number| format!("track={}", track_number),
-        info.song.track_number.as_ref(),
-    );
-    let out = command
+    command.arg("-i").arg(&excerpt_file.0);
+    let mut metadata_file = None;
+    match (cover_art, &info.cover_file) {
+        (Some(_), Some(cover_file)) if format == OutputFormat::Mp3 => {
+            command
+                .arg("-i")
+                .arg(cover_file)
+                .args(["-map", "0:a", "-map", "1:v", "-c:v", "copy"])
+                .args(["-disposition:v", "attached_pic"]);
+        }
+        (Some(cover_art), _) if matches!(format, OutputFormat::Opus | OutputFormat::Vorbis) => {
+            // Ogg files store the cover as a base64 encoded flac picture block in
+            // the comments. This is passed via a metadata file since the
+            // encoded picture can easily exceed the maximum length of an argument.
+            let metadata_file =
+                metadata_file.insert(TemporaryFile(target_file.with_extension("ffmetadata")));
+            write_picture_metadata_file(&metadata_file.0, cover_art)?;
+            command
+                .args(["-f", "ffmetadata", "-i"])
+                .arg(&metadata_file.0)
+                .args(["-map", "0:a", "-map_metadata", "1"]);
+        }
+        _ => {}
+    }
+    command.args(info.output_settings.get_ffmpeg_encoder_args());
+    for (tag, value) in join_multiple_values(tags) {
+        if let Some(key) = format.metadata_key(tag) {
+            command.arg("-metadata").arg(format!("{}={}", key, value));
+        }
+    }
+    let output = command
         .arg("-y")
         .arg(target_file.to_str().unwrap())
         .output();
-    out.map(|_| ()).context(format!(
-        "Failed to cut song: {:?} {:?} {:?} ({:?}+{:?}) (is ffmpeg installed?)",
-        &info.song.title, &info.song.album, &info.song.artist, info.start_time.time, difference,
-    ))
+    let output = output.context("Failed to run ffmpeg (is ffmpeg installed?)")?;
+    if output.status.success() {
+        Ok(())
+    } else {
+        Err(anyhow!(
+            "ffmpeg failed: {}",
+            String::from_utf8_lossy(&output.stderr)
+                .lines()
+                .last()
+                .unwrap_or("")
+        ))
+    }
+}
+
+fn write_picture_metadata_file(path: &Path, cover_art: &CoverArt) -> Result<()> {
+    let picture = base64::engine::general_purpose::STANDARD.encode(cover_art.to_flac_picture());
+    // '=' is the only character of the base64 alphabet that needs escaping
+    let contents = format!(
+        ";FFMETADATA1\nMETADATA_BLOCK_PICTURE={}\n",
+        picture.replace('=', "\\=")
+    );
+    fs::write(path, contents).context("Failed to write ffmpeg metadata file")
 }
@@ -0,0 +1,135 @@
+use std::fmt;
+
+use crate::audio_excerpt::AudioExcerpt;
+use crate::config::CONFIDENCE_SEARCH_STEP;
+use crate::config::QUIET_RELATIVE_VOLUME;
+use crate::config::SUSPICIOUS_DISTANCE_TO_QUIET;
+use crate::config::SUSPICIOUS_RELATIVE_VOLUME;
+
+/// How much a cut looks like the transition between two songs.
+/// Cuts in the middle of the music are likely to be wrong and
+/// should be reviewed by the user.
+#[derive(Clone, Copy, Debug)]
+pub struct CutConfidence {
+    /// The volume at the cut relative to the average volume of the excerpt.
+    pub relative_volume: f64,
+    /// The distance (in seconds) from the cut to the nearest quiet
+    /// position or None if there is no quiet position in the excerpt.
+    pub distance_to_quiet: Option<f64>,
+}
+
+impl CutConfidence {
+    pub fn new(excerpt: &AudioExcerpt, time: f64) -> Self {
+        let average_volume = excerpt.get_average_volume();
+        if average_volume == 0.0 {
+            // Digital silence everywhere, so every cut is as good as any other.
+            return Self {
+                relative_volume: 0.0,
+                distance_to_quiet: Some(0.0),
+            };
+        }
+        let is_quiet = |time: f64| {
+            excerpt.start.time <= time
+                && time <= excerpt.end.time
+                && excerpt.get_volume_at(time) < QUIET_RELATIVE_VOLUME * average_volume
+        };
+        let max_distance = (time - excerpt.start.time).max(excerpt.end.time - time);
+        let distance_to_quiet = (0..)
+            .map(|i| i as f64 * CONFIDENCE_SEARCH_STEP)
+            .take_while(|distance| *distance <= max_distance)
+            .find(|distance| is_quiet(time - distance) || is_quiet(time + distance));
+        Self {
+            relative_volume: excerpt.get_volume_at(time) / average_volume,
+            distance_to_quiet,
+        }
+    }
+
+    pub fn is_suspicious(&self) -> bool {
+        self.relative_volume > SUSPICIOUS_RELATIVE_VOLUME
+            || self
+                .distance_to_quiet
+                .map(|distance| distance > SUSPICIOUS_DISTANCE_TO_QUIET)
+                .unwrap_or(true)
+    }
+}
+
+impl fmt::Display for CutConfidence {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "volume {:.0}% of average, ",
+            self.relative_volume * 100.0
+        )?;
+        match self.distance_to_quiet {
+            Some(distance) => write!(f, "{:.2}s from silence", distance),
+            None => write!(f, "no silence nearby"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use hound::SampleFormat;
+    use hound::WavSpec;
+
+    use super::CutConfidence;
+    use crate::audio_excerpt::AudioExcerpt;
+    use crate::audio_time::AudioTime;
+    use crate::samples::Samples;
+
+    const SAMPLE_RATE: u32 = 10000;
+
+    /// A mono excerpt starting at 1s, with one second of
+    /// audio of each of the given amplitudes.
+    fn get_excerpt(amplitudes: &[i32]) -> AudioExcerpt {
+        let spec = WavSpec {
+            channels: 1,
+            sample_rate: SAMPLE_RATE,
+            bits_per_sample: 16,
+            sample_format: SampleFormat::Int,
+        };
+        let samples = amplitudes
+            .iter()
+            .flat_map(|amplitude| {
+                (0..SAMPLE_RATE as i32)
+                    .map(move |i| if i % 2 == 0 { *amplitude } else { -amplitude })
+            })
+            .collect();
+        AudioExcerpt {
+            samples: Samples::Int(samples),
+            start: AudioTime::from_time_and_spec(1.0, spec),
+            end: AudioTime::from_time_and_spec(1.0 + amplitudes.len() as f64, spec),
+            spec,
+        }
+    }
+
+    #[test]
+    fn cut_in_silence_is_confident() {
+        let confidence = CutConfidence::new(&get_excerpt(&[16384, 0]), 2.5);
+        assert_eq!(confidence.relative_volume, 0.0);
+        assert_eq!(confidence.distance_to_quiet, Some(0.0));
+        assert!(!confidence.is_suspicious());
+    }
+
+    #[test]
+    fn cut_in_music_is_suspicious() {
+        let confidence = CutConfidence::new(&get_excerpt(&[16384, 0]), 1.5);
+        assert!((confidence.relative_volume - 2.0).abs() < 1e-6);
+        let distance = confidence.distance_to_quiet.unwrap();
+        assert!(0.5 < distance && distance < 0.7);
+        assert!(confidence.is_suspicious());
+    }
+
+    #[test]
+    fn cut_without_silence_is_suspicious() {
+        let confidence = CutConfidence::new(&get_excerpt(&[16384, 16384]), 2.0);
+        assert_eq!(confidence.distance_to_quiet, None);
+        assert!(confidence.is_suspicious());
+    }
+
+    #[test]
+    fn cut_in_digital_silence_is_confident() {
+        let confidence = CutConfidence::new(&get_excerpt(&[0, 0]), 2.0);
+        assert!(!confidence.is_suspicious());
+    }
+}
@@ -0,0 +1,115 @@
+use serde::Deserialize;
+use serde::Serialize;
+
+use crate::config::MAX_CUT_HISTORY_LENGTH;
+
+/// The positions of all cuts at some point while editing them.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct CutState {
+    /// In seconds since the start of the buffer.
+    pub cut_times: Vec<f64>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub individually_moved_cuts: Vec<usize>,
+}
+
+/// The previous positions of the cuts, so that edits can be undone.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct CutHistory {
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    undo: Vec<CutState>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    redo: Vec<CutState>,
+}
+
+impl CutHistory {
+    pub fn is_empty(&self) -> bool {
+        self.undo.is_empty() && self.redo.is_empty()
+    }
+
+    pub fn can_undo(&self) -> bool {
+        !self.undo.is_empty()
+    }
+
+    pub fn can_redo(&self) -> bool {
+        !self.redo.is_empty()
+    }
+
+    /// Remembers the state before an edit. Any
+    /// edits that were undone can not be redone anymore.
+    pub fn push(&mut self, previous: CutState) {
+        self.undo.push(previous);
+        if self.undo.len() > MAX_CUT_HISTORY_LENGTH {
+            self.undo.remove(0);
+        }
+        self.redo.clear();
+    }
+
+    /// Returns the state before the last edit.
+    pub fn undo(&mut self, current: CutState) -> Option<CutState> {
+        let previous = self.undo.pop()?;
+        self.redo.push(current);
+        Some(previous)
+    }
+
+    /// Returns the state before the last undo.
+    pub fn redo(&mut self, current: CutState) -> Option<CutState> {
+        let next = self.redo.pop()?;
+        self.undo.push(current);
+        Some(next)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::CutHistory;
+    use super::CutState;
+    use crate::config::MAX_CUT_HISTORY_LENGTH;
+
+    fn get_state(time: f64) -> CutState {
+        CutState {
+            cut_times: vec![time],
+            individually_moved_cuts: vec![],
+        }
+    }
+
+    #[test]
+    fn undo_and_redo() {
+        let mut history = CutHistory::default();
+        assert!(history.is_empty());
+        assert_eq!(history.undo(get_state(0.0)), None);
+        history.push(get_state(1.0));
+        history.push(get_state(2.0));
+        assert_eq!(history.undo(get_state(3.0)), Some(get_state(2.0)));
+        assert_eq!(history.undo(get_state(2.0)), Some(get_state(1.0)));
+        assert!(!history.can_undo());
+        assert_eq!(history.redo(get_state(1.0)), Some(get_state(2.0)));
+        assert_eq!(history.redo(get_state(2.0)), Some(get_state(3.0)));
+        assert!(!history.can_redo());
+    }
+
+    #[test]
+    fn edit_discards_redo() {
+        let mut history = CutHistory::default();
+        history.push(get_state(1.0));
+        history.undo(get_state(2.0));
+        assert!(history.can_redo());
+        history.push(get_state(1.0));
+        assert!(!history.can_redo());
+    }
+
+    #[test]
+    fn oldest_states_are_forgotten() {
+        let mut history = CutHistory::default();
+        for i in 0..MAX_CUT_HISTORY_LENGTH + 5 {
+            history.push(get_state(i as f64));
+        }
+        let mut num_undos = 0;
+        let mut oldest = None;
+        while let Some(state) = history.undo(get_state(-1.0)) {
+            num_undos += 1;
+            oldest = Some(state);
+        }
+        assert_eq!(num_undos, MAX_CUT_HISTORY_LENGTH);
+        assert_eq!(oldest, Some(get_state(5.0)));
+    }
+}
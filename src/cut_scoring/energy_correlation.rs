@@ -0,0 +1,98 @@
+use super::get_hop_size;
+use super::CutScorer;
+use super::FeatureCurve;
+use crate::audio_excerpt::AudioExcerpt;
+use crate::config::CUT_SCORING_HOP_TIME;
+use crate::config::ENERGY_CORRELATION_MAX_LAG;
+use crate::config::ENERGY_CORRELATION_WINDOW;
+
+/// The score of positions at which there is not enough audio to compare
+/// the envelopes. Corresponds to perfect correlation, so that such
+/// positions are never preferred.
+const MAX_SCORE: f64 = 1.0;
+/// Keeps the logarithm of the energy finite in digital silence.
+const MIN_ENERGY: f64 = 1e-10;
+
+/// Scores positions by the similarity of the energy envelope before
+/// and after them. Within a song, the envelope repeats with the rhythm
+/// of the song, so that the envelope after a position correlates with
+/// the envelope before it (at some lag). At the transition to the next
+/// song, it does not, even if there is no silence in between.
+pub struct EnergyCorrelationScorer {
+    curves: Vec<FeatureCurve>,
+}
+
+impl EnergyCorrelationScorer {
+    pub fn new(excerpts: &[AudioExcerpt]) -> Self {
+        Self {
+            curves: excerpts.iter().map(get_correlation_curve).collect(),
+        }
+    }
+}
+
+impl CutScorer for EnergyCorrelationScorer {
+    fn get_score(&self, num_excerpt: usize, time: f64) -> f64 {
+        self.curves[num_excerpt].get_at(time)
+    }
+
+    /// Correlations range from -1 to 1.
+    fn get_score_scale(&self, _: usize) -> f64 {
+        1.0
+    }
+}
+
+fn get_correlation_curve(excerpt: &AudioExcerpt) -> FeatureCurve {
+    let energies = excerpt.get_energies();
+    let hop_size = get_hop_size(excerpt, CUT_SCORING_HOP_TIME);
+    let envelope: Vec<f64> = energies
+        .chunks(hop_size)
+        .map(|chunk| {
+            let energy = chunk.iter().map(|x| *x as f64).sum::<f64>() / chunk.len() as f64;
+            energy.max(MIN_ENERGY).ln()
+        })
+        .collect();
+    let window = (ENERGY_CORRELATION_WINDOW / CUT_SCORING_HOP_TIME) as usize;
+    let max_lag = (ENERGY_CORRELATION_MAX_LAG / CUT_SCORING_HOP_TIME) as usize;
+    let values = (0..envelope.len())
+        .map(|position| {
+            // Positions which cannot be compared at every lag would
+            // be favored otherwise, since their maximum is taken over
+            // fewer correlations.
+            let after = envelope.get(position..position + window);
+            match (after, position.checked_sub(window + max_lag)) {
+                (Some(after), Some(_)) => (0..=max_lag)
+                    .map(|lag| {
+                        let before = &envelope[position - lag - window..position - lag];
+                        get_correlation(before, after)
+                    })
+                    .fold(-1.0, f64::max),
+                _ => MAX_SCORE,
+            }
+        })
+        .collect();
+    FeatureCurve {
+        start_time: excerpt.start.time,
+        step: hop_size as f64 / excerpt.spec.sample_rate as f64,
+        values,
+        default: MAX_SCORE,
+    }
+}
+
+/// The pearson correlation coefficient of two sequences of the same
+/// length, which is zero if either of them is constant.
+fn get_correlation(x: &[f64], y: &[f64]) -> f64 {
+    let n = x.len() as f64;
+    let mean_x = x.iter().sum::<f64>() / n;
+    let mean_y = y.iter().sum::<f64>() / n;
+    let (mut covariance, mut variance_x, mut variance_y) = (0.0, 0.0, 0.0);
+    for (x, y) in x.iter().zip(y) {
+        covariance += (x - mean_x) * (y - mean_y);
+        variance_x += (x - mean_x).powi(2);
+        variance_y += (y - mean_y).powi(2);
+    }
+    if variance_x == 0.0 || variance_y == 0.0 {
+        0.0
+    } else {
+        covariance / (variance_x * variance_y).sqrt()
+    }
+}
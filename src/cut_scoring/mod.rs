@@ -0,0 +1,179 @@
+//! Strategies for rating how well suited a position in the recording
+//! is for a cut. The offset of the cuts is chosen such that the sum
+//! of the scores at all cut positions is minimal.
+
+mod energy_correlation;
+mod spectral_flux;
+mod volume;
+
+use std::convert::TryFrom;
+use std::str::FromStr;
+
+use serde::Deserialize;
+use serde::Serialize;
+
+use self::energy_correlation::EnergyCorrelationScorer;
+use self::spectral_flux::SpectralFluxScorer;
+use self::volume::VolumeScorer;
+use crate::audio_excerpt::AudioExcerpt;
+use crate::util::from_yaml_str;
+
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CutScoring {
+    /// Cut where the recording is quiet. Works well if the
+    /// songs begin or end with silence.
+    #[default]
+    Volume,
+    /// Cut where the spectrum changes abruptly, as it does
+    /// at the onset of a new song.
+    SpectralFlux,
+    /// Cut where the energy envelope before the cut is least similar
+    /// to the one after it. Meant for gapless albums and mixes.
+    EnergyCorrelation,
+}
+
+impl FromStr for CutScoring {
+    type Err = serde_yaml::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        from_yaml_str(s)
+    }
+}
+
+impl CutScoring {
+    pub const ALL: [CutScoring; 3] = [
+        CutScoring::Volume,
+        CutScoring::SpectralFlux,
+        CutScoring::EnergyCorrelation,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Volume => "Volume",
+            Self::SpectralFlux => "Spectral flux",
+            Self::EnergyCorrelation => "Energy correlation",
+        }
+    }
+
+    /// Analyzes the excerpts and returns a scorer for them.
+    pub fn get_scorer<'a>(&self, excerpts: &'a [AudioExcerpt]) -> Box<dyn CutScorer + 'a> {
+        match self {
+            Self::Volume => Box::new(VolumeScorer::new(excerpts)),
+            Self::SpectralFlux => Box::new(SpectralFluxScorer::new(excerpts)),
+            Self::EnergyCorrelation => Box::new(EnergyCorrelationScorer::new(excerpts)),
+        }
+    }
+}
+
+pub trait CutScorer {
+    /// The score of cutting the excerpt with the given index at
+    /// the given (absolute) time. Lower scores are better.
+    fn get_score(&self, num_excerpt: usize, time: f64) -> f64;
+
+    /// The typical magnitude of the scores of the excerpt, so that
+    /// they can be weighed against quantities of other units.
+    fn get_score_scale(&self, num_excerpt: usize) -> f64;
+}
+
+/// A feature of an excerpt, sampled at regular intervals.
+struct FeatureCurve {
+    start_time: f64,
+    step: f64,
+    values: Vec<f64>,
+    /// The value outside of the range of the curve.
+    default: f64,
+}
+
+impl FeatureCurve {
+    /// Linearly interpolates between the neighbouring values.
+    fn get_at(&self, time: f64) -> f64 {
+        let position = (time - self.start_time) / self.step;
+        if position < 0.0 || position > (self.values.len() as f64 - 1.0) {
+            return self.default;
+        }
+        let index = position.floor() as usize;
+        let fraction = position - index as f64;
+        match self.values.get(index + 1) {
+            Some(next) => self.values[index] * (1.0 - fraction) + next * fraction,
+            None => self.values[index],
+        }
+    }
+}
+
+/// Convolves the values with a gaussian of the given width (in
+/// steps), so that sharp peaks of neighbouring cuts overlap.
+fn smooth(values: &[f64], width: f64) -> Vec<f64> {
+    let radius = (3.0 * width).ceil() as isize;
+    let kernel: Vec<f64> = (-radius..=radius)
+        .map(|i| (-0.5 * (i as f64 / width).powi(2)).exp())
+        .collect();
+    (0..values.len() as isize)
+        .map(|center| {
+            let (sum, weight) = (-radius..=radius)
+                .filter_map(|i| {
+                    let value = values.get(usize::try_from(center + i).ok()?)?;
+                    Some((value, kernel[(i + radius) as usize]))
+                })
+                .fold((0.0, 0.0), |(sum, weight), (value, kernel)| {
+                    (sum + value * kernel, weight + kernel)
+                });
+            sum / weight
+        })
+        .collect()
+}
+
+/// The number of frames between two values of a feature curve.
+fn get_hop_size(excerpt: &AudioExcerpt, hop_time: f64) -> usize {
+    ((hop_time * excerpt.spec.sample_rate as f64) as usize).max(1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::smooth;
+    use super::FeatureCurve;
+
+    fn get_curve() -> FeatureCurve {
+        FeatureCurve {
+            start_time: 10.0,
+            step: 0.5,
+            values: vec![0.0, 1.0, 3.0],
+            default: 5.0,
+        }
+    }
+
+    #[test]
+    fn curve_is_interpolated() {
+        let curve = get_curve();
+        assert_eq!(curve.get_at(10.0), 0.0);
+        assert_eq!(curve.get_at(10.25), 0.5);
+        assert_eq!(curve.get_at(10.75), 2.0);
+        assert_eq!(curve.get_at(11.0), 3.0);
+    }
+
+    #[test]
+    fn curve_has_default_outside_of_range() {
+        let curve = get_curve();
+        assert_eq!(curve.get_at(9.9), 5.0);
+        assert_eq!(curve.get_at(11.1), 5.0);
+    }
+
+    #[test]
+    fn smoothing_keeps_constant_values() {
+        let smoothed = smooth(&[2.0; 10], 2.0);
+        assert!(smoothed.iter().all(|value| (value - 2.0).abs() < 1e-12));
+    }
+
+    #[test]
+    fn smoothing_spreads_peaks_symmetrically() {
+        let mut values = vec![0.0; 21];
+        values[10] = 1.0;
+        let smoothed = smooth(&values, 2.0);
+        assert!(smoothed[10] < 1.0);
+        assert!(smoothed[10] > smoothed[11]);
+        assert!(smoothed[11] > 0.0);
+        for i in 0..10 {
+            assert!((smoothed[10 - i] - smoothed[10 + i]).abs() < 1e-12);
+        }
+    }
+}
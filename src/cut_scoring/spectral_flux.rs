@@ -0,0 +1,158 @@
+use std::f64::consts::PI;
+
+use super::get_hop_size;
+use super::smooth;
+use super::CutScorer;
+use super::FeatureCurve;
+use crate::audio_excerpt::AudioExcerpt;
+use crate::config::CUT_SCORING_HOP_TIME;
+use crate::config::SPECTRAL_FLUX_COMPRESSION;
+use crate::config::SPECTRAL_FLUX_FRAME_SIZE;
+use crate::config::SPECTRAL_FLUX_SMOOTHING;
+
+/// Scores positions by the increase of the (log-compressed) magnitude
+/// spectrum, which peaks at onsets. Since a new song almost always
+/// begins with an onset, this also finds cuts between songs which
+/// are not separated by silence.
+pub struct SpectralFluxScorer {
+    curves: Vec<FeatureCurve>,
+}
+
+impl SpectralFluxScorer {
+    pub fn new(excerpts: &[AudioExcerpt]) -> Self {
+        let fft = Fft::new(SPECTRAL_FLUX_FRAME_SIZE);
+        Self {
+            curves: excerpts
+                .iter()
+                .map(|excerpt| get_flux_curve(excerpt, &fft))
+                .collect(),
+        }
+    }
+}
+
+impl CutScorer for SpectralFluxScorer {
+    fn get_score(&self, num_excerpt: usize, time: f64) -> f64 {
+        self.curves[num_excerpt].get_at(time)
+    }
+
+    /// The flux is normalized by its mean.
+    fn get_score_scale(&self, _: usize) -> f64 {
+        1.0
+    }
+}
+
+fn get_flux_curve(excerpt: &AudioExcerpt, fft: &Fft) -> FeatureCurve {
+    let samples = excerpt.get_downmixed_samples();
+    let hop_size = get_hop_size(excerpt, CUT_SCORING_HOP_TIME);
+    let mut previous_spectrum: Option<Vec<f64>> = None;
+    let mut flux = vec![];
+    for center in (0..samples.len()).step_by(hop_size) {
+        let spectrum = fft.get_log_magnitudes(&samples, center);
+        flux.push(match previous_spectrum {
+            Some(ref previous) => spectrum
+                .iter()
+                .zip(previous)
+                .map(|(current, previous)| (current - previous).max(0.0))
+                .sum(),
+            None => 0.0,
+        });
+        previous_spectrum = Some(spectrum);
+    }
+    let flux = smooth(&flux, SPECTRAL_FLUX_SMOOTHING / CUT_SCORING_HOP_TIME);
+    // Normalize, so that loud excerpts do not dominate the others.
+    let mean = flux.iter().sum::<f64>() / flux.len().max(1) as f64;
+    let values = if mean > 0.0 {
+        flux.iter().map(|flux| -flux / mean).collect()
+    } else {
+        vec![0.0; flux.len()]
+    };
+    FeatureCurve {
+        start_time: excerpt.start.time,
+        step: hop_size as f64 / excerpt.spec.sample_rate as f64,
+        values,
+        default: 0.0,
+    }
+}
+
+/// A radix-2 fast fourier transform with a hann window.
+struct Fft {
+    size: usize,
+    window: Vec<f64>,
+    twiddles: Vec<(f64, f64)>,
+}
+
+impl Fft {
+    /// The size has to be a power of two.
+    fn new(size: usize) -> Self {
+        assert!(size.is_power_of_two());
+        Self {
+            size,
+            window: (0..size)
+                .map(|i| 0.5 - 0.5 * (2.0 * PI * i as f64 / size as f64).cos())
+                .collect(),
+            twiddles: (0..size / 2)
+                .map(|k| {
+                    let (sin, cos) = (-2.0 * PI * k as f64 / size as f64).sin_cos();
+                    (cos, sin)
+                })
+                .collect(),
+        }
+    }
+
+    /// The log-compressed magnitudes of the spectrum of the frame
+    /// centered at the given sample. The signal is padded with zeros.
+    fn get_log_magnitudes(&self, samples: &[f32], center: usize) -> Vec<f64> {
+        let mut re: Vec<f64> = (0..self.size)
+            .map(|i| {
+                (center + i)
+                    .checked_sub(self.size / 2)
+                    .and_then(|index| samples.get(index))
+                    .map(|sample| *sample as f64 * self.window[i])
+                    .unwrap_or(0.0)
+            })
+            .collect();
+        let mut im = vec![0.0; self.size];
+        self.transform(&mut re, &mut im);
+        re.iter()
+            .zip(im.iter())
+            .take(self.size / 2 + 1)
+            .map(|(re, im)| (1.0 + SPECTRAL_FLUX_COMPRESSION * (re * re + im * im).sqrt()).ln())
+            .collect()
+    }
+
+    fn transform(&self, re: &mut [f64], im: &mut [f64]) {
+        let n = self.size;
+        // Reorder the input by bit-reversed indices
+        let mut j = 0;
+        for i in 1..n {
+            let mut bit = n >> 1;
+            while j & bit != 0 {
+                j ^= bit;
+                bit >>= 1;
+            }
+            j |= bit;
+            if i < j {
+                re.swap(i, j);
+                im.swap(i, j);
+            }
+        }
+        let mut len = 2;
+        while len <= n {
+            let twiddle_step = n / len;
+            for start in (0..n).step_by(len) {
+                for k in 0..len / 2 {
+                    let (cos, sin) = self.twiddles[k * twiddle_step];
+                    let a = start + k;
+                    let b = a + len / 2;
+                    let t_re = re[b] * cos - im[b] * sin;
+                    let t_im = re[b] * sin + im[b] * cos;
+                    re[b] = re[a] - t_re;
+                    im[b] = im[a] - t_im;
+                    re[a] += t_re;
+                    im[a] += t_im;
+                }
+            }
+            len <<= 1;
+        }
+    }
+}
@@ -0,0 +1,29 @@
+use super::CutScorer;
+use crate::audio_excerpt::AudioExcerpt;
+
+/// Scores positions by the volume of the recording. Using this, the
+/// offset is given by the local minimum of the convolution of the
+/// volume with a sum of dirac deltas at every cut position.
+pub struct VolumeScorer<'a> {
+    excerpts: &'a [AudioExcerpt],
+}
+
+impl<'a> VolumeScorer<'a> {
+    pub fn new(excerpts: &'a [AudioExcerpt]) -> Self {
+        Self { excerpts }
+    }
+}
+
+impl<'a> CutScorer for VolumeScorer<'a> {
+    fn get_score(&self, num_excerpt: usize, time: f64) -> f64 {
+        self.excerpts[num_excerpt].get_volume_at(time)
+    }
+
+    /// The volume depends on the recording, so the scores are
+    /// measured relative to the average volume of the excerpt.
+    fn get_score_scale(&self, num_excerpt: usize) -> f64 {
+        self.excerpts[num_excerpt]
+            .get_average_volume()
+            .max(f64::MIN_POSITIVE)
+    }
+}
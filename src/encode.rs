@@ -0,0 +1,307 @@
+//! In-process encoding of lossless output formats,
+//! which avoids depending on ffmpeg for these.
+
+use std::borrow::Cow;
+use std::fs::OpenOptions;
+use std::fs::{self};
+use std::io::Seek;
+use std::io::SeekFrom;
+use std::io::Write;
+use std::path::Path;
+
+use anyhow::anyhow;
+use anyhow::Context;
+use anyhow::Result;
+use flacenc::bitsink::ByteSink;
+use flacenc::component::BitRepr;
+use flacenc::component::MetadataBlockData;
+use flacenc::error::Verify;
+use flacenc::source::MemSource;
+use hound::WavSpec;
+
+use crate::config::MAX_FLAC_BITS_PER_SAMPLE;
+use crate::cover_art::CoverArt;
+use crate::output_format::MetadataTag;
+use crate::samples::Samples;
+
+static VORBIS_COMMENT_BLOCK_TYPE: u8 = 4;
+static PICTURE_BLOCK_TYPE: u8 = 6;
+static VENDOR_STRING: &str = "striputary";
+
+pub fn write_wav(
+    path: &Path,
+    spec: WavSpec,
+    samples: &Samples,
+    tags: &[(MetadataTag, String)],
+) -> Result<()> {
+    let mut writer = hound::WavWriter::create(path, spec)?;
+    match samples {
+        Samples::Int(samples) => {
+            for sample in samples {
+                writer.write_sample(*sample)?;
+            }
+        }
+        Samples::Float(samples) => {
+            for sample in samples {
+                writer.write_sample(*sample)?;
+            }
+        }
+    }
+    writer.finalize()?;
+    append_info_chunk(path, tags).context("While writing wav tags")
+}
+
+/// Appends a LIST chunk containing the tags to a finalized wav file
+/// and updates the size in the RIFF header accordingly.
+fn append_info_chunk(path: &Path, tags: &[(MetadataTag, String)]) -> Result<()> {
+    let mut info = b"INFO".to_vec();
+    for (tag, value) in tags {
+        if let Some(id) = tag.riff_info_id() {
+            let mut data = value.as_bytes().to_vec();
+            data.push(0);
+            info.extend(id);
+            info.extend((data.len() as u32).to_le_bytes());
+            if data.len() % 2 == 1 {
+                data.push(0);
+            }
+            info.extend(data);
+        }
+    }
+    if info.len() == b"INFO".len() {
+        return Ok(());
+    }
+    let mut file = OpenOptions::new().read(true).write(true).open(path)?;
+    let file_len = file.seek(SeekFrom::End(0))?;
+    file.write_all(b"LIST")?;
+    file.write_all(&(info.len() as u32).to_le_bytes())?;
+    file.write_all(&info)?;
+    // The RIFF size excludes the RIFF id and the size field itself
+    let riff_size = file_len + info.len() as u64;
+    file.seek(SeekFrom::Start(4))?;
+    file.write_all(&(riff_size as u32).to_le_bytes())?;
+    Ok(())
+}
+
+pub fn write_flac(
+    path: &Path,
+    spec: WavSpec,
+    samples: &Samples,
+    tags: &[(MetadataTag, String)],
+    compression_level: Option<u32>,
+    cover_art: Option<&CoverArt>,
+) -> Result<()> {
+    let config = get_flac_config(compression_level)
+        .into_verified()
+        .map_err(|(_, e)| anyhow!("Invalid flac encoder configuration: {}", e))?;
+    let (samples, bits_per_sample) = get_flac_samples(spec, samples);
+    let source = MemSource::from_samples(
+        &samples,
+        spec.channels as usize,
+        bits_per_sample as usize,
+        spec.sample_rate as usize,
+    );
+    let mut stream = flacenc::encode_with_fixed_block_size(&config, source, config.block_size)
+        .map_err(|e| anyhow!("Failed to encode flac: {}", e))?;
+    let comment = MetadataBlockData::new_unknown(VORBIS_COMMENT_BLOCK_TYPE, &vorbis_comment(tags))
+        .map_err(|e| anyhow!("Invalid flac tags: {}", e))?;
+    stream.add_metadata_block(comment);
+    if let Some(cover_art) = cover_art {
+        let picture =
+            MetadataBlockData::new_unknown(PICTURE_BLOCK_TYPE, &cover_art.to_flac_picture())
+                .map_err(|e| anyhow!("Invalid flac picture: {}", e))?;
+        stream.add_metadata_block(picture);
+    }
+    let mut sink = ByteSink::new();
+    stream
+        .write(&mut sink)
+        .map_err(|e| anyhow!("Failed to write flac stream: {}", e))?;
+    fs::write(path, sink.as_slice()).context("While writing flac file")
+}
+
+/// Flac only stores integer samples and the encoder supports bit
+/// depths up to MAX_FLAC_BITS_PER_SAMPLE, so float samples and deeper
+/// integer samples are converted to integers of that bit depth.
+fn get_flac_samples(spec: WavSpec, samples: &Samples) -> (Cow<'_, [i32]>, u16) {
+    match samples {
+        Samples::Int(samples) if spec.bits_per_sample <= MAX_FLAC_BITS_PER_SAMPLE => {
+            (Cow::Borrowed(samples), spec.bits_per_sample)
+        }
+        Samples::Int(samples) => {
+            let shift = spec.bits_per_sample - MAX_FLAC_BITS_PER_SAMPLE;
+            (
+                samples.iter().map(|sample| sample >> shift).collect(),
+                MAX_FLAC_BITS_PER_SAMPLE,
+            )
+        }
+        Samples::Float(samples) => {
+            let scale = (1i32 << (MAX_FLAC_BITS_PER_SAMPLE - 1)) as f32;
+            (
+                samples
+                    .iter()
+                    .map(|sample| (sample * scale).round().clamp(-scale, scale - 1.0) as i32)
+                    .collect(),
+                MAX_FLAC_BITS_PER_SAMPLE,
+            )
+        }
+    }
+}
+
+/// Roughly mimics the compression levels of the reference encoder:
+/// Levels up to 2 only use fixed predictors, higher levels use
+/// linear prediction of increasing order.
+fn get_flac_config(compression_level: Option<u32>) -> flacenc::config::Encoder {
+    let mut config = flacenc::config::Encoder::default();
+    if let Some(level) = compression_level {
+        config.subframe_coding.use_lpc = level > 2;
+        config.subframe_coding.qlpc.lpc_order = match level {
+            0..=5 => 8,
+            _ => 12,
+        };
+    }
+    config
+}
+
+fn vorbis_comment(tags: &[(MetadataTag, String)]) -> Vec<u8> {
+    let comments: Vec<_> = tags
+        .iter()
+        .map(|(tag, value)| format!("{}={}", tag.vorbis_comment_key(), value))
+        .collect();
+    let mut data = vec![];
+    data.extend((VENDOR_STRING.len() as u32).to_le_bytes());
+    data.extend(VENDOR_STRING.as_bytes());
+    data.extend((comments.len() as u32).to_le_bytes());
+    for comment in comments {
+        data.extend((comment.len() as u32).to_le_bytes());
+        data.extend(comment.as_bytes());
+    }
+    data
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::path::PathBuf;
+
+    use hound::SampleFormat;
+    use hound::WavSpec;
+
+    use super::get_flac_samples;
+    use super::vorbis_comment;
+    use super::write_flac;
+    use super::write_wav;
+    use crate::output_format::MetadataTag;
+    use crate::samples::Samples;
+
+    fn get_spec(bits_per_sample: u16, sample_format: SampleFormat) -> WavSpec {
+        WavSpec {
+            channels: 2,
+            sample_rate: 44100,
+            bits_per_sample,
+            sample_format,
+        }
+    }
+
+    fn get_temp_file(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("striputary_encode_{}_{}", std::process::id(), name))
+    }
+
+    fn get_tags() -> Vec<(MetadataTag, String)> {
+        vec![
+            (MetadataTag::Title, "Song".into()),
+            (MetadataTag::AlbumArtist, "Band".into()),
+            (MetadataTag::Artist, "Ab".into()),
+        ]
+    }
+
+    fn contains(bytes: &[u8], part: &[u8]) -> bool {
+        bytes.windows(part.len()).any(|window| window == part)
+    }
+
+    #[test]
+    fn wav_tags_are_appended_as_info_chunk() {
+        let path = get_temp_file("tags.wav");
+        let spec = get_spec(16, SampleFormat::Int);
+        write_wav(&path, spec, &Samples::Int(vec![1, -1, 2, -2]), &get_tags()).unwrap();
+        let bytes = fs::read(&path).unwrap();
+        let riff_size = u32::from_le_bytes([bytes[4], bytes[5], bytes[6], bytes[7]]);
+        assert_eq!(riff_size as usize, bytes.len() - 8);
+        // Values are null-terminated and padded to an even length.
+        // The album artist has no RIFF INFO id and is left out.
+        let mut list = b"LIST".to_vec();
+        list.extend(30u32.to_le_bytes());
+        list.extend(b"INFOINAM\x05\x00\x00\x00Song\x00\x00IART\x03\x00\x00\x00Ab\x00\x00");
+        assert!(bytes.ends_with(&list));
+        let samples: Vec<i32> = hound::WavReader::open(&path)
+            .unwrap()
+            .samples::<i32>()
+            .map(|sample| sample.unwrap())
+            .collect();
+        assert_eq!(samples, vec![1, -1, 2, -2]);
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn wav_without_tags_has_no_info_chunk() {
+        let path = get_temp_file("no_tags.wav");
+        write_wav(
+            &path,
+            get_spec(16, SampleFormat::Int),
+            &Samples::Int(vec![0, 0]),
+            &[],
+        )
+        .unwrap();
+        let bytes = fs::read(&path).unwrap();
+        assert!(!contains(&bytes, b"LIST"));
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn vorbis_comment_layout() {
+        let data = vorbis_comment(&[(MetadataTag::Title, "Song".into())]);
+        let mut expected = 10u32.to_le_bytes().to_vec();
+        expected.extend(b"striputary");
+        expected.extend(1u32.to_le_bytes());
+        expected.extend(10u32.to_le_bytes());
+        expected.extend(b"TITLE=Song");
+        assert_eq!(data, expected);
+    }
+
+    #[test]
+    fn flac_samples_are_converted_to_supported_bit_depths() {
+        let samples = Samples::Int(vec![1, -1]);
+        let (converted, bits) = get_flac_samples(get_spec(16, SampleFormat::Int), &samples);
+        assert_eq!((converted.as_ref(), bits), (&[1, -1][..], 16));
+        let samples = Samples::Int(vec![i32::MAX, i32::MIN, 256]);
+        let (converted, bits) = get_flac_samples(get_spec(32, SampleFormat::Int), &samples);
+        assert_eq!(
+            (converted.as_ref(), bits),
+            (&[8388607, -8388608, 1][..], 24)
+        );
+        let samples = Samples::Float(vec![1.0, -1.0, 0.5, 2.0]);
+        let (converted, bits) = get_flac_samples(get_spec(32, SampleFormat::Float), &samples);
+        assert_eq!(
+            (converted.as_ref(), bits),
+            (&[8388607, -8388608, 4194304, 8388607][..], 24)
+        );
+    }
+
+    #[test]
+    fn flac_contains_tag_block() {
+        let path = get_temp_file("tags.flac");
+        let samples = Samples::Float((0..8192).map(|i| (i as f32 * 0.01).sin()).collect());
+        write_flac(
+            &path,
+            get_spec(32, SampleFormat::Float),
+            &samples,
+            &get_tags(),
+            None,
+            None,
+        )
+        .unwrap();
+        let bytes = fs::read(&path).unwrap();
+        assert!(bytes.starts_with(b"fLaC"));
+        assert!(contains(&bytes, b"TITLE=Song"));
+        assert!(contains(&bytes, b"ALBUMARTIST=Band"));
+        fs::remove_file(&path).unwrap();
+    }
+}
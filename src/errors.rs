@@ -1,26 +1,115 @@
-use std::convert;
 use std::error::Error;
 use std::fmt;
+use std::path::PathBuf;
 
+use hound::WavSpec;
+
+/// Errors when reading an excerpt of the recorded audio.
 #[derive(Debug)]
-pub struct MissingSongError {}
+pub enum ExcerptError {
+    /// The session directory contains no buffer files.
+    NoBuffer,
+    /// The buffer file could not be opened or read.
+    UnreadableBuffer {
+        file: PathBuf,
+        error: hound::Error,
+    },
+    UnsupportedSampleFormat {
+        file: PathBuf,
+        spec: WavSpec,
+    },
+    /// A buffer segment has a different format than the first one.
+    InconsistentSegments {
+        file: PathBuf,
+    },
+    /// The requested time range is not (fully) contained in the recording.
+    /// All times are in seconds.
+    PastEndOfBuffer {
+        start: f64,
+        end: f64,
+        buffer_length: f64,
+    },
+}
 
-impl Error for MissingSongError {}
+impl Error for ExcerptError {}
 
-impl fmt::Display for MissingSongError {
+impl fmt::Display for ExcerptError {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "Oh no, something bad went down")
+        match self {
+            Self::NoBuffer => write!(f, "No buffer file found"),
+            Self::UnreadableBuffer { file, error } => {
+                write!(f, "Failed to read buffer file {:?}: {}", file, error)
+            }
+            Self::UnsupportedSampleFormat { file, spec } => write!(
+                f,
+                "Unsupported sample format in {:?}: {} bit {:?}",
+                file, spec.bits_per_sample, spec.sample_format
+            ),
+            Self::InconsistentSegments { file } => write!(
+                f,
+                "Buffer segment {:?} has a different format than the previous segments",
+                file
+            ),
+            Self::PastEndOfBuffer {
+                start,
+                end,
+                buffer_length,
+            } => write!(
+                f,
+                "The audio from {:.2}s to {:.2}s lies past the end of the recording ({:.2}s)",
+                start, end, buffer_length
+            ),
+        }
     }
 }
 
-impl convert::From<std::io::Error> for MissingSongError {
-    fn from(_error: std::io::Error) -> MissingSongError {
-        MissingSongError {}
-    }
+#[derive(Debug)]
+pub enum DbusError {
+    Connection(dbus::Error),
+    InvalidBusName(String),
+    MethodCall {
+        destination: String,
+        method: String,
+        error: dbus::Error,
+    },
+    MissingTrackId(String),
+    NoMatchingService(String),
+    MultipleMatchingServices(Vec<String>),
 }
 
-impl convert::From<hound::Error> for MissingSongError {
-    fn from(_error: hound::Error) -> MissingSongError {
-        MissingSongError {}
+impl Error for DbusError {}
+
+impl fmt::Display for DbusError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Connection(error) => write!(f, "Failed to connect to dbus: {}", error),
+            Self::InvalidBusName(name) => write!(f, "Invalid dbus bus name: {}", name),
+            Self::MethodCall {
+                destination,
+                method,
+                error,
+            } => write!(
+                f,
+                "Dbus call {} to {} failed: {}",
+                method, destination, error
+            ),
+            Self::MissingTrackId(destination) => {
+                write!(
+                    f,
+                    "{} did not send the id of the current track",
+                    destination
+                )
+            }
+            Self::NoMatchingService(base_name) => write!(
+                f,
+                "Found no matching dbus service for base name: {}",
+                base_name
+            ),
+            Self::MultipleMatchingServices(names) => write!(
+                f,
+                "Found multiple dbus services that match the service configuration: {}",
+                names.join(", ")
+            ),
+        }
     }
 }
@@ -1,6 +1,11 @@
 use crate::audio_excerpt::AudioExcerpt;
+use crate::audio_time::AudioTime;
+use crate::cut_confidence::CutConfidence;
+use crate::cut_scoring::CutScoring;
+use crate::errors::ExcerptError;
 use crate::recording_session::RecordingSession;
 use crate::song::Song;
+use crate::timeline::Pause;
 
 #[derive(Clone)]
 pub struct NamedExcerpt {
@@ -8,17 +13,46 @@ pub struct NamedExcerpt {
     pub song_before: Option<Song>,
     pub song_after: Option<Song>,
     pub num: usize,
+    /// The offset of the cut relative to the center of the excerpt.
+    pub offset: f64,
+    /// The automatically determined offset, regardless of
+    /// the cut times confirmed by the user.
+    pub offset_guess: f64,
+    /// How much the audio at the cut looks like a song transition.
+    pub confidence: CutConfidence,
+    /// The times (as received via MPRIS) at which
+    /// the song after the cut was paused.
+    pub pauses_after: Vec<Pause>,
+}
+
+impl NamedExcerpt {
+    pub fn update_confidence(&mut self, cut_time: AudioTime) {
+        self.confidence = CutConfidence::new(&self.excerpt, cut_time.time);
+    }
 }
 
-#[derive(Clone)]
 pub struct ExcerptCollection {
     pub session: RecordingSession,
     pub excerpts: Vec<NamedExcerpt>,
-    pub offset_guess: f64,
+    /// The offset of every segment of the session.
+    pub offset_guesses: Vec<f64>,
+    /// The strategy by which the offsets were determined.
+    pub cut_scoring: CutScoring,
+    pub missing_songs: Option<MissingSongs>,
+    /// Songs which were skipped, seeked in, stopped or paused without
+    /// being resumed while recording. These are not cut.
+    pub incomplete_songs: Vec<Song>,
+}
+
+/// Songs at the end of a session which are not fully contained
+/// in the recording, for example because it was stopped early.
+pub struct MissingSongs {
+    pub songs: Vec<Song>,
+    pub error: ExcerptError,
 }
 
 impl ExcerptCollection {
-    pub fn iter_excerpts<'a>(&'a self) -> Box<dyn Iterator<Item = &NamedExcerpt> + 'a> {
+    pub fn iter_excerpts<'a>(&'a self) -> Box<dyn Iterator<Item = &'a NamedExcerpt> + 'a> {
         Box::new(self.excerpts.iter())
     }
 
@@ -26,6 +60,25 @@ impl ExcerptCollection {
         &self.excerpts[num]
     }
 
+    /// The cut time of every excerpt, which is the one confirmed
+    /// by the user if there is one and the determined one otherwise.
+    pub fn get_cut_times(&self) -> Vec<AudioTime> {
+        self.iter_excerpts()
+            .map(|excerpt| {
+                excerpt
+                    .excerpt
+                    .get_absolute_time_from_time_offset(excerpt.offset)
+            })
+            .collect()
+    }
+
+    /// The excerpts whose cuts should be reviewed by the user.
+    pub fn iter_suspicious_excerpts(&self) -> impl Iterator<Item = &NamedExcerpt> {
+        self.excerpts
+            .iter()
+            .filter(|excerpt| excerpt.confidence.is_suspicious())
+    }
+
     pub fn name(&self) -> String {
         let first_song = self.session.songs.first();
         match first_song {
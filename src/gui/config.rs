@@ -1,7 +1,12 @@
+use std::time::Duration;
+
 use eframe::egui::Color32;
 use eframe::egui::Key;
 
 pub static PLOT_HEIGHT: f32 = 50.0;
+pub static DETAIL_PLOT_HEIGHT: f32 = 400.0;
+// The number of min/max pairs shown in the detail view
+pub static DETAIL_NUM_PEAKS: usize = 2000;
 pub static CUT_LINE_COLOR: Color32 = Color32::GREEN;
 pub static UNCUT_LINE_COLOR: Color32 = Color32::RED;
 
@@ -11,10 +16,39 @@ pub static UNCUT_LABEL_COLOR: Color32 = Color32::WHITE;
 pub static SELECTED_FILL_COLOR: Color32 = Color32::GRAY;
 pub static SELECTED_TEXT_COLOR: Color32 = Color32::BLACK;
 
+pub static ERROR_TEXT_COLOR: Color32 = Color32::RED;
+pub static WARNING_TEXT_COLOR: Color32 = Color32::YELLOW;
+
 pub static CUT_KEY: Key = Key::Enter;
 pub static PLAYBACK_KEY: Key = Key::Space;
+pub static AUDITION_KEY: Key = Key::A;
+pub static AUDITION_BEFORE_CUT_KEY: Key = Key::B;
+pub static STOP_PLAYBACK_KEY: Key = Key::S;
 pub static SCROLL_DOWN_KEY: Key = Key::ArrowDown;
 pub static SCROLL_UP_KEY: Key = Key::ArrowUp;
+pub static NEXT_SUSPICIOUS_CUT_KEY: Key = Key::N;
+pub static DETAIL_VIEW_KEY: Key = Key::D;
+pub static CLOSE_DETAIL_VIEW_KEY: Key = Key::Escape;
+// Move the selected cut, by a small step with Alt and a large one with Shift
+pub static NUDGE_LEFT_KEY: Key = Key::ArrowLeft;
+pub static NUDGE_RIGHT_KEY: Key = Key::ArrowRight;
+pub static SNAP_TO_ZERO_CROSSING_KEY: Key = Key::Z;
+pub static SNAP_TO_QUIETEST_TIME_KEY: Key = Key::Q;
+// Together with Ctrl, redo with Ctrl+Shift
+pub static UNDO_KEY: Key = Key::Z;
+
+// In seconds
+pub static NUDGE_STEP_SMALL: f64 = 0.001;
+pub static NUDGE_STEP: f64 = 0.01;
+pub static NUDGE_STEP_LARGE: f64 = 0.1;
+
+// In seconds. How much is played on either side of the cut when auditioning it.
+pub static AUDITION_DURATION: f64 = 2.0;
+// In seconds. The length of the beep or silence which marks the cut.
+pub static AUDITION_MARK_DURATION: f64 = 0.15;
+pub static AUDITION_BEEP_FREQUENCY: f32 = 880.0;
+pub static AUDITION_BEEP_VOLUME: f32 = 0.2;
+pub static AUDITION_QUEUE_POLL_INTERVAL: Duration = Duration::from_millis(50);
 
 pub static CUT_BUTTON_SIZE_X: f32 = 200.0;
 pub static CUT_BUTTON_SIZE_Y: f32 = 50.0;
@@ -25,3 +59,6 @@ pub static MIN_NUM_PLOTS_SHOWN: i32 = 5;
 
 pub static CUT_MARKER_WIDTH: f32 = 2.0;
 pub static CUT_MARKER_COLOR: Color32 = Color32::YELLOW;
+pub static INDIVIDUAL_CUT_MARKER_COLOR: Color32 = Color32::LIGHT_GREEN;
+pub static SUSPICIOUS_CUT_MARKER_COLOR: Color32 = Color32::from_rgb(255, 128, 0);
+pub static GUESS_MARKER_COLOR: Color32 = Color32::LIGHT_BLUE;
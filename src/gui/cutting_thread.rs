@@ -4,6 +4,8 @@ use std::sync::mpsc::Sender;
 use std::thread::JoinHandle;
 use std::thread::{self};
 
+use anyhow::Error;
+
 use crate::config;
 use crate::cut::cut_song;
 use crate::cut::CutInfo;
@@ -13,14 +15,17 @@ use crate::song::Song;
 struct CuttingThread {
     pub to_cut: DataStream<CutInfo>,
     song_sender: Sender<Song>,
+    error_sender: Sender<Error>,
 }
 
 impl CuttingThread {
     pub fn cutting_loop(&mut self) {
         loop {
             if let Some(info) = self.to_cut.get_data_mut().pop() {
-                cut_song(&info).unwrap();
-                self.song_sender.send(info.song).unwrap();
+                match cut_song(&info) {
+                    Ok(()) => self.song_sender.send(info.song).unwrap(),
+                    Err(e) => self.error_sender.send(e).unwrap(),
+                }
             }
             self.to_cut.update(config::RECV_CUT_INFO_TIMEOUT);
         }
@@ -28,10 +33,15 @@ impl CuttingThread {
 }
 
 impl CuttingThread {
-    fn new(receiver: Receiver<CutInfo>, song_sender: Sender<Song>) -> Self {
+    fn new(
+        receiver: Receiver<CutInfo>,
+        song_sender: Sender<Song>,
+        error_sender: Sender<Error>,
+    ) -> Self {
         CuttingThread {
             to_cut: DataStream::new(receiver),
             song_sender,
+            error_sender,
         }
     }
 }
@@ -40,17 +50,22 @@ pub struct CuttingThreadHandle {
     _handle: JoinHandle<()>,
     sender: Sender<CutInfo>,
     cut_songs: DataStream<Song>,
+    errors: DataStream<Error>,
 }
 
 impl Default for CuttingThreadHandle {
     fn default() -> Self {
         let (sender, receiver) = channel();
         let (song_sender, song_receiver) = channel();
-        let handle = thread::spawn(|| CuttingThread::new(receiver, song_sender).cutting_loop());
+        let (error_sender, error_receiver) = channel();
+        let handle = thread::spawn(|| {
+            CuttingThread::new(receiver, song_sender, error_sender).cutting_loop()
+        });
         CuttingThreadHandle {
             _handle: handle,
             sender,
             cut_songs: DataStream::new(song_receiver),
+            errors: DataStream::new(error_receiver),
         }
     }
 }
@@ -64,6 +79,11 @@ impl CuttingThreadHandle {
 
     pub fn get_cut_songs(&mut self) -> &[Song] {
         self.cut_songs.update(config::RECV_CUT_SONG_TIMEOUT);
-        &self.cut_songs.get_data()
+        self.cut_songs.get_data()
+    }
+
+    pub fn get_errors(&mut self) -> &[Error] {
+        self.errors.update(config::RECV_CUT_SONG_TIMEOUT);
+        self.errors.get_data()
     }
 }
@@ -0,0 +1,186 @@
+use super::plot::ExcerptPlot;
+use crate::audio_time::AudioTime;
+
+/// Which markers follow when the user moves a cut marker.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum MarkerMoveMode {
+    /// Only the moved marker. The cut is then overridden individually
+    /// and is not moved along with the others anymore.
+    ThisCut,
+    /// The moved marker and all markers after it, except for those
+    /// which were moved individually. Helps with drift.
+    #[default]
+    ThisAndFollowing,
+    /// Every marker, including those which were moved individually.
+    All,
+}
+
+impl MarkerMoveMode {
+    pub const ALL: [MarkerMoveMode; 3] = [
+        MarkerMoveMode::ThisCut,
+        MarkerMoveMode::ThisAndFollowing,
+        MarkerMoveMode::All,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::ThisCut => "This cut only",
+            Self::ThisAndFollowing => "This and following",
+            Self::All => "All",
+        }
+    }
+
+    /// Whether the marker of the cut with the given index
+    /// follows when the marker of the clicked cut is moved.
+    pub fn moves(&self, num: usize, clicked_num: usize) -> bool {
+        match self {
+            Self::ThisCut => num == clicked_num,
+            Self::ThisAndFollowing => num >= clicked_num,
+            Self::All => true,
+        }
+    }
+}
+
+/// Where the moved markers end up.
+#[derive(Copy, Clone, Debug)]
+pub enum MarkerMovement {
+    /// At the given offset from the start of their excerpt, as
+    /// when clicking into the plot.
+    ToOffset(AudioTime),
+    /// Shifted by the given number of seconds, which keeps the
+    /// differences between their offsets, as when nudging.
+    By(f64),
+}
+
+/// Moves the marker of the clicked cut along with the markers given
+/// by the mode. When moving the following markers, those which were
+/// moved individually before keep their position. Moving all markers
+/// resets them as well.
+pub fn move_markers(
+    plots: &mut [ExcerptPlot],
+    clicked_num: usize,
+    movement: MarkerMovement,
+    mode: MarkerMoveMode,
+) {
+    for plot in plots.iter_mut() {
+        if plot.excerpt.num == clicked_num {
+            plot.move_marker(movement);
+            plot.moved_individually = mode == MarkerMoveMode::ThisCut;
+        } else if mode == MarkerMoveMode::All {
+            plot.move_marker(movement);
+            plot.moved_individually = false;
+        } else if !plot.moved_individually && mode.moves(plot.excerpt.num, clicked_num) {
+            plot.move_marker(movement);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use hound::SampleFormat;
+    use hound::WavSpec;
+
+    use super::move_markers;
+    use super::MarkerMoveMode;
+    use super::MarkerMovement;
+    use crate::audio_excerpt::AudioExcerpt;
+    use crate::audio_time::AudioTime;
+    use crate::cut_confidence::CutConfidence;
+    use crate::excerpt_collection::NamedExcerpt;
+    use crate::gui::plot::ExcerptPlot;
+    use crate::samples::Samples;
+
+    /// A plot of a ten second excerpt starting at the
+    /// given time, with the cut at the given offset.
+    fn get_plot(num: usize, start: f64, cut_offset: f64) -> ExcerptPlot {
+        let spec = WavSpec {
+            channels: 1,
+            sample_rate: 1000,
+            bits_per_sample: 16,
+            sample_format: SampleFormat::Int,
+        };
+        let excerpt = NamedExcerpt {
+            excerpt: AudioExcerpt {
+                samples: Samples::Int(vec![0; 10000]),
+                start: AudioTime::from_time_and_spec(start, spec),
+                end: AudioTime::from_time_and_spec(start + 10.0, spec),
+                spec,
+            },
+            song_before: None,
+            song_after: None,
+            num,
+            offset: 0.0,
+            offset_guess: 0.0,
+            confidence: CutConfidence {
+                relative_volume: 0.0,
+                distance_to_quiet: Some(0.0),
+            },
+            pauses_after: vec![],
+        };
+        ExcerptPlot::new(
+            excerpt,
+            AudioTime::from_time_and_spec(start + cut_offset, spec),
+        )
+    }
+
+    fn get_cut_offsets(plots: &[ExcerptPlot]) -> Vec<f64> {
+        plots
+            .iter()
+            .map(|plot| plot.cut_time.time - plot.excerpt.excerpt.start.time)
+            .collect()
+    }
+
+    fn assert_close(actual: Vec<f64>, expected: Vec<f64>) {
+        assert_eq!(actual.len(), expected.len());
+        for (actual, expected) in actual.iter().zip(expected.iter()) {
+            assert!((actual - expected).abs() < 1e-9, "{:?}", actual);
+        }
+    }
+
+    #[test]
+    fn nudging_keeps_offsets_of_following_markers() {
+        let mut plots = vec![get_plot(0, 100.0, 5.0), get_plot(1, 200.0, 6.5)];
+        move_markers(
+            &mut plots,
+            0,
+            MarkerMovement::By(0.001),
+            MarkerMoveMode::ThisAndFollowing,
+        );
+        assert_close(get_cut_offsets(&plots), vec![5.001, 6.501]);
+    }
+
+    #[test]
+    fn clicking_sets_offsets_of_following_markers() {
+        let mut plots = vec![get_plot(0, 100.0, 5.0), get_plot(1, 200.0, 6.5)];
+        let offset = AudioTime::from_time_same_spec(4.0, plots[0].cut_time);
+        move_markers(
+            &mut plots,
+            0,
+            MarkerMovement::ToOffset(offset),
+            MarkerMoveMode::ThisAndFollowing,
+        );
+        assert_close(get_cut_offsets(&plots), vec![4.0, 4.0]);
+    }
+
+    #[test]
+    fn individually_moved_markers_stay_unless_all_are_moved() {
+        let mut plots = vec![get_plot(0, 100.0, 5.0), get_plot(1, 200.0, 6.5)];
+        plots[1].moved_individually = true;
+        let mode = MarkerMoveMode::ThisAndFollowing;
+        move_markers(&mut plots, 0, MarkerMovement::By(1.0), mode);
+        assert_close(get_cut_offsets(&plots), vec![6.0, 6.5]);
+        move_markers(&mut plots, 0, MarkerMovement::By(1.0), MarkerMoveMode::All);
+        assert_close(get_cut_offsets(&plots), vec![7.0, 7.5]);
+        assert!(!plots[1].moved_individually);
+    }
+
+    #[test]
+    fn moved_markers() {
+        let get_moved = |mode: MarkerMoveMode| -> Vec<usize> {
+            (0..4).filter(|num| mode.moves(*num, 1)).collect()
+        };
+        assert_eq!(get_moved(MarkerMoveMode::ThisCut), vec![1]);
+        assert_eq!(get_moved(MarkerMoveMode::ThisAndFollowing), vec![1, 2, 3]);
+        assert_eq!(get_moved(MarkerMoveMode::All), vec![0, 1, 2, 3]);
+    }
+}
@@ -1,5 +1,6 @@
 mod config;
 mod cutting_thread;
+mod marker_move_mode;
 mod playback;
 mod plot;
 mod session_manager;
@@ -8,10 +9,13 @@ use std::path::Path;
 
 use eframe::egui::Button;
 use eframe::egui::Color32;
+use eframe::egui::ComboBox;
+use eframe::egui::Key;
 use eframe::egui::Label;
 use eframe::egui::Layout;
 use eframe::egui::Response;
 use eframe::egui::RichText;
+use eframe::egui::TextEdit;
 use eframe::egui::TextStyle;
 use eframe::egui::Ui;
 use eframe::egui::Vec2;
@@ -21,14 +25,29 @@ use eframe::App;
 use eframe::Frame;
 
 use self::cutting_thread::CuttingThreadHandle;
+use self::marker_move_mode::move_markers;
+use self::marker_move_mode::MarkerMoveMode;
+use self::marker_move_mode::MarkerMovement;
+use self::playback::audition_cut;
 use self::playback::play_excerpt;
+use self::playback::AuditionMode;
+use self::playback::AuditionSettings;
 use self::playback::PlaybackThreadHandle;
 use self::plot::ExcerptPlot;
+use crate::audio_excerpt::AudioExcerpt;
 use crate::audio_time::AudioTime;
+use crate::config::SNAP_TO_QUIETEST_TIME_MAX_DISTANCE;
+use crate::config::SNAP_TO_ZERO_CROSSING_MAX_DISTANCE;
+use crate::cut::get_cut_info;
 use crate::cut::CutInfo;
+use crate::cut_history::CutState;
+use crate::cut_scoring::CutScoring;
 use crate::excerpt_collection::ExcerptCollection;
 use crate::gui::session_manager::SessionIdentifier;
 use crate::gui::session_manager::SessionManager;
+use crate::offset_mode::OffsetMode;
+use crate::output_format::OutputSettings;
+use crate::pause_mode::PauseMode;
 use crate::recording::recording_thread_handle_status::RecordingThreadHandleStatus;
 use crate::run_args::RunArgs;
 use crate::service_config::Service;
@@ -45,61 +64,77 @@ struct SongIdentifier {
 pub struct StriputaryGui {
     service: Service,
     sink_type: SinkType,
+    pause_mode: PauseMode,
+    output_settings: OutputSettings,
     collection: Option<ExcerptCollection>,
+    /// The reason why the selected session could not be loaded
+    load_error: Option<anyhow::Error>,
     plots: Vec<ExcerptPlot>,
     scroll_position: usize,
     cut_thread: CuttingThreadHandle,
     record_thread: RecordingThreadHandleStatus,
     current_playback: Option<(SongIdentifier, PlaybackThreadHandle)>,
     last_touched_song: Option<SongIdentifier>,
+    audition_settings: AuditionSettings,
+    /// The song whose cut is shown in the detail view, if any.
+    detail_song: Option<SongIdentifier>,
+    marker_move_mode: MarkerMoveMode,
     should_repaint: bool,
     session_manager: SessionManager,
+    cut_times_changed: bool,
+    /// The cut positions as they were last saved, which
+    /// are remembered in the history once they are changed.
+    saved_cut_state: Option<CutState>,
+    /// The offset of the selected cut as entered by the user.
+    offset_input: String,
 }
 
 impl StriputaryGui {
-    pub fn new(dir: &Path, service: Service, sink_type: SinkType) -> Self {
-        let session_manager = SessionManager::new(dir);
+    pub fn new(
+        dir: &Path,
+        service: Service,
+        sink_type: SinkType,
+        pause_mode: PauseMode,
+        offset_mode: OffsetMode,
+        output_settings: OutputSettings,
+    ) -> Self {
+        let session_manager = SessionManager::new(dir, offset_mode);
         let mut gui = Self {
             service,
             collection: None,
+            load_error: None,
             plots: vec![],
             scroll_position: 0,
             cut_thread: CuttingThreadHandle::default(),
             record_thread: RecordingThreadHandleStatus::new_stopped(),
             current_playback: None,
             last_touched_song: None,
+            audition_settings: AuditionSettings::default(),
+            detail_song: None,
+            marker_move_mode: MarkerMoveMode::default(),
             should_repaint: false,
             session_manager,
             sink_type,
+            pause_mode,
+            output_settings,
+            cut_times_changed: false,
+            saved_cut_state: None,
+            offset_input: String::new(),
         };
         gui.load_selected_session();
         gui
     }
 
     fn cut_songs(&self) {
-        match self.collection {
-            Some(ref collection) => {
-                let cut_info = self.get_cut_info(collection);
-                self.cut_thread.send_cut_infos(cut_info);
-            }
-            None => {}
+        if let Some(ref collection) = self.collection {
+            let cut_info = self.get_cut_info(collection);
+            self.cut_thread.send_cut_infos(cut_info);
         }
     }
 
     fn get_cut_info(&self, collection: &ExcerptCollection) -> Vec<CutInfo> {
-        let mut cut_info: Vec<CutInfo> = vec![];
-        for (i, (plot_start, plot_end)) in self.plots.iter().zip(self.plots[1..].iter()).enumerate()
-        {
-            let song = plot_start.excerpt.song_after.as_ref().unwrap();
-            cut_info.push(CutInfo::new(
-                &collection.session,
-                song.clone(),
-                plot_start.cut_time,
-                plot_end.cut_time,
-                i,
-            ));
-        }
-        cut_info
+        let cut_times: Vec<_> = self.plots.iter().map(|plot| plot.cut_time).collect();
+        get_cut_info(collection, &cut_times, &self.output_settings)
     }
 
     fn mark_cut_songs(&mut self) {
@@ -112,20 +147,41 @@ impl StriputaryGui {
     }
 
     fn play_last_touched_song(&mut self) {
+        self.play_around_last_touched_cut(play_excerpt);
+    }
+
+    fn audition_last_touched_cut(&mut self, mode: AuditionMode) {
+        let settings = self.audition_settings;
+        self.play_around_last_touched_cut(|excerpt, cut_time| {
+            audition_cut(excerpt, cut_time, mode, settings)
+        });
+    }
+
+    /// Replaces the current playback by the one started by play,
+    /// which gets the cut time relative to the start of the excerpt.
+    fn play_around_last_touched_cut(
+        &mut self,
+        play: impl FnOnce(&AudioExcerpt, AudioTime) -> PlaybackThreadHandle,
+    ) {
         if let Some(last_touched) = self.last_touched_song {
+            self.stop_playback();
             let plot = &self.plots[last_touched.song_index];
             let excerpt = &plot.excerpt.excerpt;
-            if let Some((_, ref thread)) = self.current_playback {
-                thread.shut_down();
-            }
             self.current_playback = Some((
                 last_touched,
-                play_excerpt(excerpt, excerpt.get_relative_time(plot.cut_time)),
+                play(excerpt, excerpt.get_relative_time(plot.cut_time)),
             ));
         }
     }
 
+    fn stop_playback(&mut self) {
+        if let Some((_, ref thread)) = self.current_playback.take() {
+            thread.shut_down();
+        }
+    }
+
     fn start_recording(&mut self) {
+        self.save_cut_times();
         self.session_manager.select_new();
         self.load_selected_session();
         if !self.record_thread.is_running() {
@@ -136,26 +192,182 @@ impl StriputaryGui {
     }
 
     fn get_run_args(&self) -> Option<RunArgs> {
-        let service_config = ServiceConfig::from_service(self.service).unwrap();
+        let service_config = ServiceConfig::from_service(&self.service)
+            .map_err(|e| println!("{:?}", e))
+            .ok()?;
         Some(RunArgs {
             session_dir: self.session_manager.get_currently_selected()?,
             service_config: service_config.clone(),
             sink_type: self.sink_type.clone(),
+            pause_mode: self.pause_mode,
         })
     }
 
     fn select_session(&mut self, identifier: SessionIdentifier) {
+        self.save_cut_times();
         self.session_manager.select(identifier);
         self.load_selected_session();
     }
 
     fn load_selected_session(&mut self) {
-        self.collection = self.session_manager.get_currently_selected_collection();
-        if let Some(ref collection) = self.collection {
-            self.plots = self.get_plots(collection);
+        let (collection, load_error) = match self
+            .session_manager
+            .get_currently_selected_collection(self.record_thread.is_running())
+        {
+            Ok(collection) => (collection, None),
+            Err(e) => (None, Some(e)),
+        };
+        self.collection = collection;
+        self.load_error = load_error;
+        self.detail_song = None;
+        self.plots = match self.collection {
+            Some(ref collection) => self.get_plots(collection),
+            None => vec![],
+        };
+        self.saved_cut_state = self.collection.as_ref().map(|_| self.get_cut_state());
+    }
+
+    fn get_cut_state(&self) -> CutState {
+        CutState {
+            cut_times: self.plots.iter().map(|plot| plot.cut_time.time).collect(),
+            individually_moved_cuts: self
+                .plots
+                .iter()
+                .filter(|plot| plot.moved_individually)
+                .map(|plot| plot.excerpt.num)
+                .collect(),
+        }
+    }
+
+    fn set_cut_state(&mut self, state: &CutState) {
+        for (plot, time) in self.plots.iter_mut().zip(state.cut_times.iter()) {
+            plot.cut_time = AudioTime::from_time_same_spec(*time, plot.cut_time);
+            plot.moved_individually = state.individually_moved_cuts.contains(&plot.excerpt.num);
+        }
+        // The state comes from the history, so it should not be added to it again.
+        self.saved_cut_state = Some(self.get_cut_state());
+        self.cut_times_changed = true;
+        self.finish_cut_edit();
+    }
+
+    /// Updates the confidences of the cuts and saves them.
+    fn finish_cut_edit(&mut self) {
+        if self.cut_times_changed {
+            for plot in self.plots.iter_mut() {
+                plot.update_confidence();
+            }
+        }
+        self.save_cut_times();
+    }
+
+    /// Moves the selected cut to the given time (in seconds since the
+    /// start of the buffer), along with the cuts given by the mode.
+    fn move_selected_cut(&mut self, get_time: impl Fn(&ExcerptPlot) -> f64, mode: MarkerMoveMode) {
+        if let Some(song) = self.last_touched_song {
+            let plot = &self.plots[song.song_index];
+            let excerpt = &plot.excerpt.excerpt;
+            let time = get_time(plot).clamp(excerpt.start.time, excerpt.end.time);
+            // The other cuts are shifted by the same amount, so that
+            // their individually determined offsets are kept.
+            let movement = MarkerMovement::By(time - plot.cut_time.time);
+            self.move_markers(song, movement, mode);
+            self.cut_times_changed = true;
+            self.finish_cut_edit();
+        }
+    }
+
+    fn nudge_selected_cut(&mut self, step: f64) {
+        self.move_selected_cut(|plot| plot.cut_time.time + step, self.marker_move_mode);
+    }
+
+    /// Snapping is a local correction, so only the selected cut is moved.
+    fn snap_selected_cut_to_zero_crossing(&mut self) {
+        self.move_selected_cut(
+            |plot| {
+                plot.excerpt
+                    .excerpt
+                    .get_nearest_zero_crossing(
+                        plot.cut_time.time,
+                        SNAP_TO_ZERO_CROSSING_MAX_DISTANCE,
+                    )
+                    .unwrap_or(plot.cut_time.time)
+            },
+            MarkerMoveMode::ThisCut,
+        );
+    }
+
+    fn snap_selected_cut_to_quietest_time(&mut self) {
+        self.move_selected_cut(
+            |plot| {
+                plot.excerpt
+                    .excerpt
+                    .get_quietest_time_near(plot.cut_time.time, SNAP_TO_QUIETEST_TIME_MAX_DISTANCE)
+            },
+            MarkerMoveMode::ThisCut,
+        );
+    }
+
+    /// Sets the selected cut to the entered offset
+    /// (in milliseconds relative to the automatically determined cut).
+    fn apply_offset_input(&mut self) {
+        match self.offset_input.trim().parse::<f64>() {
+            Ok(offset_ms) => {
+                self.move_selected_cut(
+                    |plot| plot.get_guessed_cut_time() + offset_ms / 1000.0,
+                    self.marker_move_mode,
+                );
+                self.offset_input.clear();
+            }
+            Err(_) => println!("Invalid offset: {}", self.offset_input),
         }
     }
 
+    fn undo(&mut self) {
+        let current = self.get_cut_state();
+        let previous = self
+            .collection
+            .as_mut()
+            .and_then(|collection| collection.session.cut_history.undo(current));
+        if let Some(previous) = previous {
+            self.set_cut_state(&previous);
+        }
+    }
+
+    fn redo(&mut self) {
+        let current = self.get_cut_state();
+        let next = self
+            .collection
+            .as_mut()
+            .and_then(|collection| collection.session.cut_history.redo(current));
+        if let Some(next) = next {
+            self.set_cut_state(&next);
+        }
+    }
+
+    /// Store the current cut positions in the session file, so that
+    /// manual adjustments survive switching sessions or restarting.
+    /// The previously saved positions are added to the history.
+    fn save_cut_times(&mut self) {
+        if !self.cut_times_changed {
+            return;
+        }
+        let state = self.get_cut_state();
+        let previous_state = self.saved_cut_state.replace(state.clone());
+        if let Some(ref mut collection) = self.collection {
+            if let Some(previous_state) = previous_state {
+                if previous_state != state {
+                    collection.session.cut_history.push(previous_state);
+                }
+            }
+            collection.session.cut_times = Some(state.cut_times);
+            collection.session.individually_moved_cuts = state.individually_moved_cuts;
+            if let Err(e) = collection.session.save() {
+                println!("Failed to save cut positions: {}", e);
+            }
+        }
+        self.cut_times_changed = false;
+    }
+
     fn scroll(&mut self, diff: i32) {
         let num_plots = self
             .collection
@@ -180,9 +392,17 @@ impl StriputaryGui {
             .min_width(config::MIN_SIDE_BAR_WIDTH)
             .show(ctx, |ui| {
                 let cut_button = self.add_large_button(ui, "Cut all songs");
-                if cut_button.clicked() || ctx.input().key_pressed(config::CUT_KEY) {
+                if cut_button.clicked() || shortcut_pressed(ctx, config::CUT_KEY) {
                     self.cut_songs();
                 }
+                self.add_cutting_error_messages(ui);
+                self.add_session_messages(ui);
+                self.add_suspicious_cut_navigation(ui, ctx);
+                self.add_cut_scoring_selection(ui);
+                self.add_marker_move_mode_selection(ui);
+                self.add_undo_buttons(ui);
+                self.add_selected_cut_controls(ui);
+                self.add_audition_controls(ui);
                 self.add_dir_selection_bar(ui);
             });
     }
@@ -231,6 +451,213 @@ impl StriputaryGui {
         ui.add(label);
     }
 
+    fn add_cutting_error_messages(&mut self, ui: &mut Ui) {
+        for error in self.cut_thread.get_errors() {
+            let label = Label::new(RichText::new(format!("{:#}", error)).color(Color32::RED));
+            ui.add(label);
+        }
+    }
+
+    /// Tells the user why (parts of) the selected session could not be loaded.
+    fn add_session_messages(&self, ui: &mut Ui) {
+        if let Some(ref error) = self.load_error {
+            let label = Label::new(
+                RichText::new(format!("Failed to load session: {:#}", error))
+                    .color(config::ERROR_TEXT_COLOR),
+            );
+            ui.add(label);
+        }
+        if let Some(missing_songs) = self
+            .collection
+            .as_ref()
+            .and_then(|collection| collection.missing_songs.as_ref())
+        {
+            let label = Label::new(
+                RichText::new(format!(
+                    "{} songs at the end of the session were not recorded fully: {}",
+                    missing_songs.songs.len(),
+                    missing_songs.error
+                ))
+                .color(config::WARNING_TEXT_COLOR),
+            );
+            ui.add(label);
+        }
+        if let Some(collection) = self.collection.as_ref() {
+            for song in collection.incomplete_songs.iter() {
+                let label = Label::new(
+                    RichText::new(format!("Not played fully: {}", song))
+                        .color(config::WARNING_TEXT_COLOR),
+                );
+                ui.add(label);
+            }
+        }
+    }
+
+    /// Points the user to the cuts which most likely need to be
+    /// adjusted, so that not every cut has to be reviewed.
+    fn add_suspicious_cut_navigation(&mut self, ui: &mut Ui, ctx: &egui::Context) {
+        let num_suspicious = self
+            .plots
+            .iter()
+            .filter(|plot| plot.excerpt.confidence.is_suspicious())
+            .count();
+        if num_suspicious == 0 {
+            return;
+        }
+        ui.add(Label::new(
+            RichText::new(format!("{} suspicious cuts", num_suspicious))
+                .color(config::SUSPICIOUS_CUT_MARKER_COLOR),
+        ));
+        let button = ui.add(Button::new("Next suspicious cut"));
+        if button.clicked() || shortcut_pressed(ctx, config::NEXT_SUSPICIOUS_CUT_KEY) {
+            self.scroll_to_next_suspicious_cut();
+        }
+    }
+
+    fn scroll_to_next_suspicious_cut(&mut self) {
+        let num_plots = self.plots.len();
+        let next = (1..=num_plots)
+            .map(|i| (self.scroll_position + i) % num_plots)
+            .find(|index| self.plots[*index].excerpt.confidence.is_suspicious());
+        if let Some(next) = next {
+            self.scroll_position = next;
+        }
+    }
+
+    /// Lets the user choose how the cuts of the selected session are
+    /// determined, which is stored in the session.
+    fn add_cut_scoring_selection(&mut self, ui: &mut Ui) {
+        let current = match self.collection {
+            Some(ref collection) => collection.cut_scoring,
+            None => return,
+        };
+        let mut selected = current;
+        ComboBox::from_label("Cut detection")
+            .selected_text(current.name())
+            .show_ui(ui, |ui| {
+                for cut_scoring in CutScoring::ALL {
+                    ui.selectable_value(&mut selected, cut_scoring, cut_scoring.name());
+                }
+            })
+            .response
+            .on_hover_text("Changing this discards the adjusted cuts. Undo restores them.");
+        if selected != current {
+            self.set_cut_scoring(selected);
+        }
+    }
+
+    /// The confirmed cut positions were found with the previous
+    /// strategy and would hide the new one, so they are discarded.
+    /// They remain in the history, so that they can be restored.
+    fn set_cut_scoring(&mut self, cut_scoring: CutScoring) {
+        self.save_cut_times();
+        let state = self.get_cut_state();
+        if let Some(ref mut collection) = self.collection {
+            if collection.session.cut_times.take().is_some() {
+                collection.session.cut_history.push(state);
+            }
+            collection.session.individually_moved_cuts.clear();
+            collection.session.cut_scoring = Some(cut_scoring);
+            if let Err(e) = collection.session.save() {
+                println!("Failed to save cut detection strategy: {}", e);
+            }
+        }
+        self.load_selected_session();
+    }
+
+    fn add_marker_move_mode_selection(&mut self, ui: &mut Ui) {
+        ComboBox::from_label("Move markers")
+            .selected_text(self.marker_move_mode.name())
+            .show_ui(ui, |ui| {
+                for mode in MarkerMoveMode::ALL {
+                    ui.selectable_value(&mut self.marker_move_mode, mode, mode.name());
+                }
+            });
+    }
+
+    fn add_undo_buttons(&mut self, ui: &mut Ui) {
+        let (can_undo, can_redo) = match self.collection {
+            Some(ref collection) => (
+                collection.session.cut_history.can_undo(),
+                collection.session.cut_history.can_redo(),
+            ),
+            None => (false, false),
+        };
+        ui.horizontal(|ui| {
+            if ui.add_enabled(can_undo, Button::new("Undo")).clicked() {
+                self.undo();
+            }
+            if ui.add_enabled(can_redo, Button::new("Redo")).clicked() {
+                self.redo();
+            }
+        });
+    }
+
+    fn add_selected_cut_controls(&mut self, ui: &mut Ui) {
+        let plot = match self.last_touched_song {
+            Some(song) => &self.plots[song.song_index],
+            None => return,
+        };
+        ui.label(format!(
+            "Selected cut: {:+.1} ms",
+            (plot.cut_time.time - plot.get_guessed_cut_time()) * 1000.0
+        ));
+        let (input, button) = ui
+            .horizontal(|ui| {
+                let input = ui.add(
+                    TextEdit::singleline(&mut self.offset_input)
+                        .hint_text("Offset in ms")
+                        .desired_width(100.0),
+                );
+                (input, ui.add(Button::new("Set")))
+            })
+            .inner;
+        if button.clicked() || (input.lost_focus() && ui.input().key_pressed(Key::Enter)) {
+            self.apply_offset_input();
+        }
+        ui.horizontal(|ui| {
+            if ui.add(Button::new("Zero crossing")).clicked() {
+                self.snap_selected_cut_to_zero_crossing();
+            }
+            if ui.add(Button::new("Quietest point")).clicked() {
+                self.snap_selected_cut_to_quietest_time();
+            }
+        });
+    }
+
+    fn add_audition_controls(&mut self, ui: &mut Ui) {
+        if self.last_touched_song.is_none() {
+            return;
+        }
+        ui.horizontal(|ui| {
+            if ui.add(Button::new("Play across cut")).clicked() {
+                self.audition_last_touched_cut(AuditionMode::AcrossCut);
+            }
+            if ui.add(Button::new("Play before cut")).clicked() {
+                self.audition_last_touched_cut(AuditionMode::BeforeCut);
+            }
+            if ui.add(Button::new("Stop")).clicked() {
+                self.stop_playback();
+            }
+        });
+        ui.horizontal(|ui| {
+            ui.checkbox(&mut self.audition_settings.beep_at_cut, "Beep at cut");
+            ui.checkbox(&mut self.audition_settings.looping, "Loop");
+        });
+    }
+
+    /// The selected mode, unless overridden by holding a modifier key.
+    fn get_marker_move_mode(&self, ctx: &egui::Context) -> MarkerMoveMode {
+        let modifiers = ctx.input().modifiers;
+        if modifiers.shift {
+            MarkerMoveMode::ThisCut
+        } else if modifiers.command {
+            MarkerMoveMode::All
+        } else {
+            self.marker_move_mode
+        }
+    }
+
     fn add_labels_for_recorded_songs(&self, ui: &mut Ui) {
         let songs = self.record_thread.get_songs();
         for song in songs.iter().rev() {
@@ -252,59 +679,98 @@ impl StriputaryGui {
             .map(move |(i, s)| (i + self.scroll_position, s))
     }
 
-    fn move_all_markers_after(&mut self, clicked_song: SongIdentifier, offset: AudioTime) {
-        for plot in self.plots.iter_mut() {
-            if plot.excerpt.num >= clicked_song.song_index {
-                plot.move_marker_to_offset(offset);
-            }
-        }
+    fn move_markers(
+        &mut self,
+        clicked_song: SongIdentifier,
+        movement: MarkerMovement,
+        mode: MarkerMoveMode,
+    ) {
+        move_markers(&mut self.plots, clicked_song.song_index, movement, mode);
     }
 
-    fn add_plot_labels(ui: &mut Ui, plot: &ExcerptPlot) {
+    /// Adds the titles of the songs around the cut along with a
+    /// button with the given text. Returns whether it was clicked.
+    fn add_plot_labels(ui: &mut Ui, plot: &ExcerptPlot, button_text: &str) -> bool {
+        let mut clicked = false;
         ui.horizontal(|ui| {
             add_plot_label(
                 ui,
                 plot.excerpt.song_before.as_ref(),
                 plot.finished_cutting_song_before,
             );
+            if plot.moved_individually {
+                ui.add(Label::new(
+                    RichText::new("Moved individually").color(config::INDIVIDUAL_CUT_MARKER_COLOR),
+                ));
+            }
+            if plot.excerpt.confidence.is_suspicious() {
+                ui.add(Label::new(
+                    RichText::new(format!("Suspicious cut: {}", plot.excerpt.confidence))
+                        .color(config::SUSPICIOUS_CUT_MARKER_COLOR),
+                ));
+            }
             ui.with_layout(Layout::right_to_left(Align::Center), |ui| {
                 add_plot_label(
                     ui,
                     plot.excerpt.song_after.as_ref(),
                     plot.finished_cutting_song_after,
                 );
+                clicked = ui.small_button(button_text).clicked();
             });
         });
+        clicked
+    }
+
+    fn toggle_detail_view(&mut self, song: SongIdentifier) {
+        if self.detail_song == Some(song) {
+            self.detail_song = None;
+        } else {
+            self.detail_song = Some(song);
+            self.last_touched_song = Some(song);
+        }
     }
 
     fn set_playback_marker_and_return_finished_state(
         plot: &mut ExcerptPlot,
         current_playback: &PlaybackThreadHandle,
     ) -> bool {
-        let playback_time_relative = current_playback.get_elapsed_audio_time();
-        let playback_time_absolute = plot.excerpt.excerpt.start + playback_time_relative;
-        if playback_time_absolute < plot.excerpt.excerpt.end {
-            plot.show_playback_marker_at(playback_time_absolute);
-            return false;
-        } else {
-            return true;
+        let playback_time_absolute = current_playback
+            .get_elapsed_audio_time()
+            .map(|relative| plot.excerpt.excerpt.start + relative)
+            .filter(|absolute| *absolute < plot.excerpt.excerpt.end);
+        match playback_time_absolute {
+            Some(time) => {
+                plot.show_playback_marker_at(time);
+                false
+            }
+            None => true,
         }
     }
 
     fn add_central_panel(&mut self, ctx: &egui::Context) {
         let mouse_pos = ctx.input().pointer.interact_pos();
         let mut clicked_song_and_offset: Option<(SongIdentifier, AudioTime)> = None;
+        let mut clicked_detail_offset: Option<AudioTime> = None;
+        let mut toggled_detail_song: Option<SongIdentifier> = None;
         let panel_height = ctx.used_size().y;
         let num_plots_shown = (panel_height / config::PLOT_HEIGHT).ceil() as i32;
         egui::CentralPanel::default().show(ctx, |ui| {
             if self.record_thread.is_running() {
                 self.add_labels_for_recorded_songs(ui);
+            } else if let Some(detail_song) = self.detail_song {
+                let plot = &self.plots[detail_song.song_index];
+                if Self::add_plot_labels(ui, plot, "Back") {
+                    toggled_detail_song = Some(detail_song);
+                }
+                clicked_detail_offset = plot.show_detail_and_get_offset(ui);
             } else {
                 for (plot_song, plot) in self
                     .enumerate_visible_plots(num_plots_shown)
                     .map(|(song_index, plot)| (SongIdentifier { song_index }, plot))
                 {
-                    Self::add_plot_labels(ui, plot);
+                    if Self::add_plot_labels(ui, plot, "Details") {
+                        toggled_detail_song = Some(plot_song);
+                    }
                     let offset = plot.show_and_get_offset(plot_song.song_index, ui, mouse_pos);
                     if let Some(offset) = offset {
                         clicked_song_and_offset = Some((plot_song, offset));
@@ -314,29 +780,95 @@ impl StriputaryGui {
         });
         if let Some((clicked_song, offset)) = clicked_song_and_offset {
             self.last_touched_song = Some(clicked_song);
-            self.move_all_markers_after(clicked_song, offset);
+            self.move_markers(
+                clicked_song,
+                MarkerMovement::ToOffset(offset),
+                self.get_marker_move_mode(ctx),
+            );
+            self.cut_times_changed = true;
+        }
+        if let (Some(detail_song), Some(offset)) = (self.detail_song, clicked_detail_offset) {
+            // Only the cut in the detail view is moved, since this is for fine adjustments.
+            self.move_markers(
+                detail_song,
+                MarkerMovement::ToOffset(offset),
+                MarkerMoveMode::ThisCut,
+            );
+            self.last_touched_song = Some(detail_song);
+            self.cut_times_changed = true;
+        }
+        if let Some(song) = toggled_detail_song {
+            self.toggle_detail_view(song);
+        }
+        if ctx.input().pointer.any_released() {
+            self.finish_cut_edit();
         }
     }
 
     fn keyboard_control(&mut self, ctx: &egui::Context) {
-        if ctx.input().key_pressed(config::SCROLL_UP_KEY) {
+        if shortcut_pressed(ctx, config::SCROLL_UP_KEY) {
             self.scroll(-1);
         }
-        if ctx.input().key_pressed(config::SCROLL_DOWN_KEY) {
+        if shortcut_pressed(ctx, config::SCROLL_DOWN_KEY) {
             self.scroll(1);
         }
-        if ctx.input().key_pressed(config::PLAYBACK_KEY) {
+        if shortcut_pressed(ctx, config::PLAYBACK_KEY) {
             self.play_last_touched_song();
         }
+        if shortcut_pressed(ctx, config::AUDITION_KEY) {
+            self.audition_last_touched_cut(AuditionMode::AcrossCut);
+        }
+        if shortcut_pressed(ctx, config::AUDITION_BEFORE_CUT_KEY) {
+            self.audition_last_touched_cut(AuditionMode::BeforeCut);
+        }
+        if shortcut_pressed(ctx, config::STOP_PLAYBACK_KEY) {
+            self.stop_playback();
+        }
+        if shortcut_pressed(ctx, config::DETAIL_VIEW_KEY) {
+            if let Some(last_touched) = self.last_touched_song {
+                self.toggle_detail_view(last_touched);
+            }
+        }
+        if shortcut_pressed(ctx, config::CLOSE_DETAIL_VIEW_KEY) {
+            self.detail_song = None;
+        }
+        let modifiers = ctx.input().modifiers;
+        if modifiers.command && shortcut_pressed(ctx, config::UNDO_KEY) {
+            match modifiers.shift {
+                true => self.redo(),
+                false => self.undo(),
+            }
+        }
+        let nudge_step = if modifiers.alt {
+            config::NUDGE_STEP_SMALL
+        } else if modifiers.shift {
+            config::NUDGE_STEP_LARGE
+        } else {
+            config::NUDGE_STEP
+        };
+        if shortcut_pressed(ctx, config::NUDGE_LEFT_KEY) {
+            self.nudge_selected_cut(-nudge_step);
+        }
+        if shortcut_pressed(ctx, config::NUDGE_RIGHT_KEY) {
+            self.nudge_selected_cut(nudge_step);
+        }
+        if !modifiers.command {
+            if shortcut_pressed(ctx, config::SNAP_TO_ZERO_CROSSING_KEY) {
+                self.snap_selected_cut_to_zero_crossing();
+            }
+            if shortcut_pressed(ctx, config::SNAP_TO_QUIETEST_TIME_KEY) {
+                self.snap_selected_cut_to_quietest_time();
+            }
+        }
     }
 
     fn handle_playback_markers(&mut self) {
-        for mut plot in self.plots.iter_mut() {
+        for plot in self.plots.iter_mut() {
             plot.hide_playback_marker();
             if let Some((playback_song, ref current_playback_handle)) = self.current_playback {
                 if playback_song.song_index == plot.excerpt.num {
                     self.should_repaint = !Self::set_playback_marker_and_return_finished_state(
-                        &mut plot,
+                        plot,
                         current_playback_handle,
                     );
                 }
@@ -346,15 +878,15 @@ impl StriputaryGui {
 
     fn get_plots(&self, collection: &ExcerptCollection) -> Vec<ExcerptPlot> {
         collection
-            .excerpts
-            .iter()
-            .map(|excerpt| {
-                ExcerptPlot::new(
-                    excerpt.clone(),
-                    excerpt
-                        .excerpt
-                        .get_absolute_time_from_time_offset(collection.offset_guess),
-                )
+            .iter_excerpts()
+            .zip(collection.get_cut_times())
+            .map(|(excerpt, cut_time)| {
+                let mut plot = ExcerptPlot::new(excerpt.clone(), cut_time);
+                plot.moved_individually = collection
+                    .session
+                    .individually_moved_cuts
+                    .contains(&excerpt.num);
+                plot
             })
             .collect()
     }
@@ -373,6 +905,16 @@ impl App for StriputaryGui {
         }
         self.keyboard_control(ctx);
     }
+
+    fn on_close_event(&mut self) -> bool {
+        self.save_cut_times();
+        true
+    }
+}
+
+/// Whether the key was pressed, unless the user is typing into a text field.
+fn shortcut_pressed(ctx: &egui::Context, key: Key) -> bool {
+    !ctx.wants_keyboard_input() && ctx.input().key_pressed(key)
 }
 
 pub fn get_label_color(finished_cutting: bool) -> Color32 {
@@ -384,7 +926,7 @@ pub fn get_label_color(finished_cutting: bool) -> Color32 {
 
 fn add_plot_label(ui: &mut Ui, song: Option<&Song>, finished_cutting: bool) {
     let color = get_label_color(finished_cutting);
-    if let Some(ref song) = song {
+    if let Some(song) = song {
         ui.add(Label::new(
             RichText::new(format_title(&song.title)).color(color),
         ));
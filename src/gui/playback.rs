@@ -1,12 +1,22 @@
 use std::sync::mpsc::channel;
+use std::sync::mpsc::Receiver;
+use std::sync::mpsc::RecvTimeoutError;
 use std::sync::mpsc::Sender;
 use std::thread;
 use std::time::Duration;
 use std::time::SystemTime;
 
+use rodio::source::SineWave;
+use rodio::source::Zero;
 use rodio::OutputStream;
 use rodio::Sink;
+use rodio::Source;
 
+use super::config::AUDITION_BEEP_FREQUENCY;
+use super::config::AUDITION_BEEP_VOLUME;
+use super::config::AUDITION_DURATION;
+use super::config::AUDITION_MARK_DURATION;
+use super::config::AUDITION_QUEUE_POLL_INTERVAL;
 use crate::audio_excerpt::AudioExcerpt;
 use crate::audio_excerpt::AudioExcerptSource;
 use crate::audio_time::AudioTime;
@@ -15,6 +25,21 @@ pub struct PlaybackThreadHandle {
     shutdown_sender: Sender<ShutdownSignal>,
     start_system_time: SystemTime,
     start_audio_time: AudioTime,
+    timeline: PlaybackTimeline,
+}
+
+/// How the elapsed playback time maps to positions in the excerpt.
+enum PlaybackTimeline {
+    /// The excerpt is played until its end.
+    UntilEnd,
+    /// A part of the excerpt is played, interrupted by a
+    /// mark at the cut. All lengths are in seconds.
+    Audition {
+        before_cut: f64,
+        mark: f64,
+        after_cut: f64,
+        looping: bool,
+    },
 }
 
 impl PlaybackThreadHandle {
@@ -22,20 +47,74 @@ impl PlaybackThreadHandle {
         self.shutdown_sender.send(ShutdownSignal).unwrap();
     }
 
-    pub fn get_elapsed_audio_time(&self) -> AudioTime {
+    /// The position (relative to the start of the excerpt) which is
+    /// currently played or None if the playback has finished.
+    pub fn get_elapsed_audio_time(&self) -> Option<AudioTime> {
         let time_expired = SystemTime::now().duration_since(self.start_system_time);
         let time_expired_secs = time_expired
             .unwrap_or(Duration::from_millis(0))
             .as_secs_f64();
-        AudioTime::from_time_same_spec(
-            self.start_audio_time.time + time_expired_secs,
+        let position = match self.timeline {
+            PlaybackTimeline::UntilEnd => time_expired_secs,
+            PlaybackTimeline::Audition {
+                before_cut,
+                mark,
+                after_cut,
+                looping,
+            } => {
+                let length = before_cut + mark + after_cut;
+                if length <= 0.0 {
+                    return None;
+                }
+                let time_expired_secs = match looping {
+                    true => time_expired_secs % length,
+                    false if time_expired_secs < length => time_expired_secs,
+                    false => return None,
+                };
+                if time_expired_secs < before_cut {
+                    time_expired_secs
+                } else if time_expired_secs < before_cut + mark {
+                    before_cut
+                } else {
+                    time_expired_secs - mark
+                }
+            }
+        };
+        Some(AudioTime::from_time_same_spec(
+            self.start_audio_time.time + position,
             self.start_audio_time,
-        )
+        ))
     }
 }
 
 pub struct ShutdownSignal;
 
+/// Which part of the excerpt around the cut is auditioned.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum AuditionMode {
+    /// The end of the song before the cut, followed
+    /// by the beginning of the song after it.
+    AcrossCut,
+    /// Only the end of the song before the cut.
+    BeforeCut,
+}
+
+#[derive(Copy, Clone, Debug)]
+pub struct AuditionSettings {
+    /// Whether the cut is marked by a beep instead of silence.
+    pub beep_at_cut: bool,
+    pub looping: bool,
+}
+
+impl Default for AuditionSettings {
+    fn default() -> Self {
+        Self {
+            beep_at_cut: true,
+            looping: false,
+        }
+    }
+}
+
 pub fn play_excerpt(excerpt: &AudioExcerpt, start_time: AudioTime) -> PlaybackThreadHandle {
     let cloned = excerpt.clone();
     let (shutdown_sender, shutdown_receiver) = channel();
@@ -45,11 +124,107 @@ pub fn play_excerpt(excerpt: &AudioExcerpt, start_time: AudioTime) -> PlaybackTh
         let sink = Sink::try_new(&stream_handle).unwrap();
         sink.append(source);
         sink.play();
-        if let Ok(_) = shutdown_receiver.recv() {}
+        let _ = shutdown_receiver.recv();
+    });
+    PlaybackThreadHandle {
+        shutdown_sender,
+        start_system_time: SystemTime::now(),
+        start_audio_time: start_time,
+        timeline: PlaybackTimeline::UntilEnd,
+    }
+}
+
+/// Plays the audio around the cut (given relative to the start of
+/// the excerpt), so that both sides of the cut can be judged.
+pub fn audition_cut(
+    excerpt: &AudioExcerpt,
+    cut_time: AudioTime,
+    mode: AuditionMode,
+    settings: AuditionSettings,
+) -> PlaybackThreadHandle {
+    let excerpt_length = excerpt.end.time - excerpt.start.time;
+    let before_cut = AUDITION_DURATION.min(cut_time.time).max(0.0);
+    let (mark, after_cut) = match mode {
+        AuditionMode::AcrossCut => (
+            AUDITION_MARK_DURATION,
+            AUDITION_DURATION
+                .min(excerpt_length - cut_time.time)
+                .max(0.0),
+        ),
+        AuditionMode::BeforeCut => (0.0, 0.0),
+    };
+    let start_time = AudioTime::from_time_same_spec(cut_time.time - before_cut, cut_time);
+    let end_time = AudioTime::from_time_same_spec(cut_time.time + after_cut, cut_time);
+    let cloned = excerpt.clone();
+    let (shutdown_sender, shutdown_receiver) = channel();
+    thread::spawn(move || {
+        let (_stream, stream_handle) = OutputStream::try_default().unwrap();
+        let sink = Sink::try_new(&stream_handle).unwrap();
+        let append_audition = |sink: &Sink| {
+            sink.append(AudioExcerptSource::new_between(
+                cloned.clone(),
+                start_time,
+                cut_time,
+            ));
+            if mode == AuditionMode::AcrossCut {
+                let mark_duration = Duration::from_secs_f64(mark);
+                if settings.beep_at_cut {
+                    sink.append(
+                        SineWave::new(AUDITION_BEEP_FREQUENCY)
+                            .take_duration(mark_duration)
+                            .amplify(AUDITION_BEEP_VOLUME),
+                    );
+                } else {
+                    sink.append(
+                        Zero::<f32>::new(cloned.spec.channels, cloned.spec.sample_rate)
+                            .take_duration(mark_duration),
+                    );
+                }
+                sink.append(AudioExcerptSource::new_between(
+                    cloned.clone(),
+                    cut_time,
+                    end_time,
+                ));
+            }
+        };
+        append_audition(&sink);
+        sink.play();
+        if settings.looping {
+            loop_audition(&sink, &shutdown_receiver, append_audition);
+        } else {
+            let _ = shutdown_receiver.recv();
+        }
     });
     PlaybackThreadHandle {
         shutdown_sender,
         start_system_time: SystemTime::now(),
         start_audio_time: start_time,
+        timeline: PlaybackTimeline::Audition {
+            before_cut,
+            mark,
+            after_cut,
+            looping: settings.looping,
+        },
+    }
+}
+
+/// Queues the next repetition of the audition before the current one
+/// ends, so that there is no gap between them.
+fn loop_audition(
+    sink: &Sink,
+    shutdown_receiver: &Receiver<ShutdownSignal>,
+    append_audition: impl Fn(&Sink),
+) {
+    let num_sources_per_audition = sink.len();
+    append_audition(sink);
+    loop {
+        match shutdown_receiver.recv_timeout(AUDITION_QUEUE_POLL_INTERVAL) {
+            Err(RecvTimeoutError::Timeout) => {
+                if sink.len() <= num_sources_per_audition {
+                    append_audition(sink);
+                }
+            }
+            _ => return,
+        }
     }
 }
@@ -3,7 +3,11 @@ use eframe::egui::*;
 
 use super::config::CUT_MARKER_COLOR;
 use super::config::CUT_MARKER_WIDTH;
+use super::config::INDIVIDUAL_CUT_MARKER_COLOR;
+use super::config::SUSPICIOUS_CUT_MARKER_COLOR;
 use super::config::{self};
+use super::marker_move_mode::MarkerMovement;
+use crate::audio_excerpt::Peak;
 use crate::audio_time::AudioTime;
 use crate::excerpt_collection::NamedExcerpt;
 use crate::song::Song;
@@ -14,6 +18,8 @@ pub struct ExcerptPlot {
     pub finished_cutting_song_before: bool,
     pub finished_cutting_song_after: bool,
     pub playback_marker: Option<AudioTime>,
+    /// Whether the cut was moved independently of the others.
+    pub moved_individually: bool,
 }
 
 impl ExcerptPlot {
@@ -24,6 +30,7 @@ impl ExcerptPlot {
             finished_cutting_song_before: false,
             finished_cutting_song_after: false,
             playback_marker: None,
+            moved_individually: false,
         }
     }
 
@@ -49,6 +56,16 @@ impl ExcerptPlot {
         }
     }
 
+    fn get_cut_marker_color(&self) -> Color32 {
+        if self.excerpt.confidence.is_suspicious() {
+            SUSPICIOUS_CUT_MARKER_COLOR
+        } else if self.moved_individually {
+            INDIVIDUAL_CUT_MARKER_COLOR
+        } else {
+            CUT_MARKER_COLOR
+        }
+    }
+
     pub fn show_playback_marker_at(&mut self, audio_time: AudioTime) {
         self.playback_marker = Some(audio_time);
     }
@@ -70,10 +87,37 @@ impl ExcerptPlot {
         }
     }
 
+    pub fn update_confidence(&mut self) {
+        self.excerpt.update_confidence(self.cut_time);
+    }
+
     pub fn move_marker_to_offset(&mut self, offset: AudioTime) {
         self.cut_time = self.excerpt.excerpt.start + offset;
     }
 
+    /// Moves the marker by the given number of seconds,
+    /// without leaving the excerpt.
+    pub fn shift_marker(&mut self, seconds: f64) {
+        let excerpt = &self.excerpt.excerpt;
+        let time = (self.cut_time.time + seconds).clamp(excerpt.start.time, excerpt.end.time);
+        self.cut_time = AudioTime::from_time_same_spec(time, self.cut_time);
+    }
+
+    pub fn move_marker(&mut self, movement: MarkerMovement) {
+        match movement {
+            MarkerMovement::ToOffset(offset) => self.move_marker_to_offset(offset),
+            MarkerMovement::By(seconds) => self.shift_marker(seconds),
+        }
+    }
+
+    /// The automatically determined cut time (in seconds since the start of the buffer).
+    pub fn get_guessed_cut_time(&self) -> f64 {
+        self.excerpt
+            .excerpt
+            .get_absolute_time_from_time_offset(self.excerpt.offset_guess)
+            .time
+    }
+
     pub fn show_and_get_offset(
         &self,
         num: usize,
@@ -98,7 +142,7 @@ impl ExcerptPlot {
                     .line(line_after.color(self.get_line_color(self.finished_cutting_song_after)));
                 plot_ui.vline(VLine::new(self.cut_time.time).stroke(Stroke {
                     width: CUT_MARKER_WIDTH,
-                    color: CUT_MARKER_COLOR,
+                    color: self.get_cut_marker_color(),
                 }));
                 if let Some(time) = self.playback_marker {
                     plot_ui.vline(VLine::new(time.time));
@@ -114,4 +158,60 @@ impl ExcerptPlot {
             })
             .filter(|_| response.dragged())
     }
+
+    /// Shows the waveform around the cut in a zoomable and pannable
+    /// plot. The time axis is in milliseconds relative to the
+    /// automatically determined cut. Returns the clicked position.
+    pub fn show_detail_and_get_offset(&self, ui: &mut Ui) -> Option<AudioTime> {
+        let excerpt = &self.excerpt.excerpt;
+        let guess = self.get_guessed_cut_time();
+        let to_plot_x = |time: f64| (time - guess) * 1000.0;
+        let from_plot_x = |x: f64| guess + x / 1000.0;
+        let mut clicked_time: Option<f64> = None;
+        Plot::new("detail")
+            .height(config::DETAIL_PLOT_HEIGHT)
+            .include_x(to_plot_x(excerpt.start.time))
+            .include_x(to_plot_x(excerpt.end.time))
+            .include_y(-1.0)
+            .include_y(1.0)
+            .show_background(false)
+            .show(ui, |plot_ui| {
+                let bounds = plot_ui.plot_bounds();
+                // Only the visible part is resolved, so that zooming in shows more detail.
+                let (start, end) = if bounds.min()[0] < bounds.max()[0] {
+                    (from_plot_x(bounds.min()[0]), from_plot_x(bounds.max()[0]))
+                } else {
+                    (excerpt.start.time, excerpt.end.time)
+                };
+                let peaks = excerpt.get_peaks(start, end, config::DETAIL_NUM_PEAKS);
+                let get_line = |get_value: fn(&Peak) -> f32| {
+                    Line::new(PlotPoints::new(
+                        peaks
+                            .iter()
+                            .map(|peak| [to_plot_x(peak.time), get_value(peak) as f64])
+                            .collect(),
+                    ))
+                    .color(config::UNCUT_LINE_COLOR)
+                };
+                plot_ui.line(get_line(|peak| peak.min));
+                plot_ui.line(get_line(|peak| peak.max));
+                plot_ui.vline(VLine::new(0.0).color(config::GUESS_MARKER_COLOR));
+                plot_ui.vline(VLine::new(to_plot_x(self.cut_time.time)).stroke(Stroke {
+                    width: CUT_MARKER_WIDTH,
+                    color: self.get_cut_marker_color(),
+                }));
+                if let Some(time) = self.playback_marker {
+                    plot_ui.vline(VLine::new(to_plot_x(time.time)));
+                }
+                if plot_ui.plot_clicked() {
+                    clicked_time = plot_ui
+                        .pointer_coordinate()
+                        .map(|pointer| from_plot_x(pointer.x));
+                }
+            });
+        clicked_time.map(|time| {
+            let absolute_time = AudioTime::from_time_same_spec(time, excerpt.start);
+            excerpt.get_relative_time(absolute_time)
+        })
+    }
 }
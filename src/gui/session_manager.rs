@@ -1,13 +1,13 @@
-use std::fs::DirEntry;
-use std::fs::{self};
 use std::path::Path;
 use std::path::PathBuf;
 
 use anyhow::Result;
-use chrono::Local;
 
 use crate::cut::get_excerpt_collection;
 use crate::excerpt_collection::ExcerptCollection;
+use crate::offset_mode::OffsetMode;
+use crate::recording_session::get_new_session_dir;
+use crate::recording_session::get_session_dirs;
 use crate::recording_session::RecordingSession;
 
 #[derive(Clone, Copy, PartialEq, Eq)]
@@ -17,22 +17,20 @@ pub enum SessionIdentifier {
 }
 
 pub struct SessionManager {
-    output_dir: PathBuf,
     dirs: Vec<PathBuf>,
     new_dir: PathBuf,
     selected: Option<SessionIdentifier>,
+    offset_mode: OffsetMode,
 }
 
 impl SessionManager {
-    pub fn new(dir: &Path) -> Self {
-        let mut dirs = get_dirs(dir).unwrap();
-        dirs.sort();
-        dirs.reverse();
+    pub fn new(dir: &Path, offset_mode: OffsetMode) -> Self {
+        let dirs = get_session_dirs(dir).unwrap();
         let mut manager = Self {
-            output_dir: dir.into(),
             dirs,
-            new_dir: get_new_name(dir),
+            new_dir: get_new_session_dir(dir),
             selected: None,
+            offset_mode,
         };
         manager.select_latest();
         manager
@@ -43,9 +41,9 @@ impl SessionManager {
     }
 
     fn select_latest(&mut self) {
-        let dirs = get_dirs(&self.output_dir).unwrap();
-        self.selected = dirs
-            .into_iter()
+        self.selected = self
+            .dirs
+            .iter()
             .enumerate()
             .max_by_key(|(_, dir)| dir.metadata().unwrap().modified().unwrap())
             .map(|(index, _)| SessionIdentifier::Old(index));
@@ -68,19 +66,28 @@ impl SessionManager {
         })
     }
 
-    pub fn get_currently_selected_collection(&self) -> Option<ExcerptCollection> {
-        let session_dir = self.get_currently_selected()?;
-        if session_dir.is_dir() {
-            RecordingSession::from_parent_dir(&session_dir)
-                .map(|session| get_excerpt_collection(session))
-                .map_err(|x| {
-                    println!("{}", x);
-                    x
-                })
-                .ok()
-        } else {
-            None
+    /// Loads the selected session. Returns None if no session is
+    /// selected or the selected one has not been recorded yet.
+    /// The buffer files of the new session are not repaired while
+    /// it is being recorded, since their headers are still written.
+    pub fn get_currently_selected_collection(
+        &self,
+        recording: bool,
+    ) -> Result<Option<ExcerptCollection>> {
+        let session_dir = match self.get_currently_selected() {
+            Some(session_dir) if session_dir.is_dir() => session_dir,
+            _ => return Ok(None),
+        };
+        let session = RecordingSession::from_parent_dir(&session_dir)?;
+        if !(recording && self.selected == Some(SessionIdentifier::New)) {
+            repair_buffer_files(&session);
         }
+        let cut_scoring = session.cut_scoring.unwrap_or_default();
+        Ok(Some(get_excerpt_collection(
+            session,
+            self.offset_mode,
+            cut_scoring,
+        )?))
     }
 
     pub fn iter_relative_paths_with_indices(
@@ -100,28 +107,15 @@ impl SessionManager {
     }
 }
 
-fn get_entries_with_predicate<F>(dir: &Path, predicate: F) -> Result<impl Iterator<Item = PathBuf>>
-where
-    F: Fn(&Path) -> bool,
-{
-    let entries = fs::read_dir(dir)?;
-    let dir_entries: std::io::Result<Vec<DirEntry>> = entries.collect();
-    Ok(dir_entries?
-        .into_iter()
-        .map(|entry| entry.path())
-        .filter(move |path| predicate(path))
-        .map(|path| path.to_owned()))
-}
-
-fn iter_dirs(dir: &Path) -> Result<impl Iterator<Item = PathBuf>> {
-    get_entries_with_predicate(dir, Path::is_dir)
-}
-
-pub fn get_dirs(dir: &Path) -> Result<Vec<PathBuf>> {
-    Ok(iter_dirs(dir)?.collect())
-}
-
-fn get_new_name(output_dir: &Path) -> PathBuf {
-    let date_string = Local::now().format("%Y-%m-%d-%H-%M-%S").to_string();
-    output_dir.join(&date_string)
+/// Sessions whose recording was interrupted have broken buffer
+/// headers, so we repair them before loading the audio.
+fn repair_buffer_files(session: &RecordingSession) {
+    match session.repair_buffer_files() {
+        Ok(repaired) => {
+            for file in repaired {
+                println!("Repaired header of {:?}", file);
+            }
+        }
+        Err(e) => println!("{:?}", e),
+    }
 }
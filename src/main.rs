@@ -1,27 +1,47 @@
 pub mod args;
 pub mod audio_excerpt;
 pub mod audio_time;
+mod cli;
 pub mod config;
 pub mod config_file;
+mod cover_art;
 pub mod cut;
+pub mod cut_confidence;
+pub mod cut_history;
+pub mod cut_scoring;
 pub mod data_stream;
+mod encode;
 pub mod errors;
 pub mod excerpt_collection;
 pub mod gui;
+pub mod offset_mode;
+pub mod output_format;
+pub mod path_template;
+pub mod pause_mode;
+pub mod playback_event;
 pub mod recording;
 pub mod recording_session;
 pub mod run_args;
+pub mod samples;
 pub mod service_config;
 mod sink_type;
 pub mod song;
+pub mod timeline;
+mod util;
 pub mod wav;
+mod wav_repair;
 
 use std::path::Path;
 
+use anyhow::anyhow;
 use anyhow::Result;
+use args::Command;
 use args::Opts;
 use clap::Parser;
 use config_file::ConfigFile;
+use offset_mode::OffsetMode;
+use output_format::OutputSettings;
+use pause_mode::PauseMode;
 use service_config::Service;
 use sink_type::SinkType;
 
@@ -31,22 +51,20 @@ fn main() -> Result<(), anyhow::Error> {
     let args = Opts::parse();
     let config_file = ConfigFile::read();
     if let Err(ref e) = config_file {
-        println!("{}", e);
+        println!("{:?}", e);
     }
     let config_file = config_file.ok();
+    let output_settings = get_output_settings(&args, config_file.as_ref())?;
     let output_dir = args
         .output_dir
         .or(config_file.as_ref().map(|file| file.output_dir.clone()));
-    let service = args
-        .service
-        .or(config_file
-            .as_ref()
-            .and_then(|file: &ConfigFile| file.service))
-        .unwrap_or_else(|| {
-            let service = Service::default();
-            println!("No service specified in command line args or config file. Using default.");
-            service
-        });
+    let user_services = config_file
+        .as_ref()
+        .map(|file| file.services.clone())
+        .unwrap_or_default();
+    let service_name = args.service.or(config_file
+        .as_ref()
+        .and_then(|file: &ConfigFile| file.service.clone()));
     let monitor = args.monitor
         | config_file
             .as_ref()
@@ -57,17 +75,102 @@ fn main() -> Result<(), anyhow::Error> {
     } else {
         SinkType::Normal
     };
-    println!("Using service: {}", service);
-    match output_dir {
-        Some(dir) => {
-            Ok(run_gui(&dir, service, sink_type))
+    let offset_mode = args
+        .offset_mode
+        .or(config_file
+            .as_ref()
+            .and_then(|file: &ConfigFile| file.offset_mode))
+        .unwrap_or_default();
+    let pause_mode = args
+        .pause_mode
+        .or(config_file
+            .as_ref()
+            .and_then(|file: &ConfigFile| file.pause_mode))
+        .unwrap_or_default();
+    let dir = output_dir.ok_or_else(|| anyhow!("Need an output folder - either pass it as a command line argument or specify it in the config file (probably ~/.config/striputary/config.yaml"));
+    match args.command.unwrap_or(Command::Gui) {
+        Command::Gui => {
+            let service = get_service(service_name, &user_services)?;
+            recording::interrupt::install_handler()?;
+            run_gui(
+                &dir?,
+                service,
+                sink_type,
+                pause_mode,
+                offset_mode,
+                output_settings,
+            );
+            Ok(())
+        }
+        Command::Record => {
+            let service = get_service(service_name, &user_services)?;
+            recording::interrupt::install_handler()?;
+            cli::record(&dir?, &service, sink_type, pause_mode)
         }
-        None => panic!("Need an output folder - either pass it as a command line argument or specify it in the config file (probably ~/.config/striputary/config.yaml")
+        Command::Cut {
+            session,
+            cut_scoring,
+        } => cli::cut(&dir?, &session, offset_mode, cut_scoring, &output_settings),
+        Command::ListSessions => cli::list_sessions(&dir?),
+        Command::Repair { session } => cli::repair(&dir?, &session),
+        Command::Discover => cli::discover(&user_services),
     }
 }
 
-fn run_gui(dir: &Path, service: Service, sink_type: SinkType) {
-    let app = StriputaryGui::new(dir, service, sink_type);
+/// Resolves the service to record. Only done for the commands which
+/// record, so that the others still work with a misconfigured service.
+fn get_service(name: Option<String>, user_services: &[Service]) -> Result<Service> {
+    let service = match name {
+        Some(name) => Service::from_name(&name, user_services)?,
+        None => {
+            println!("No service specified in command line args or config file. Using default.");
+            Service::from_name(config::DEFAULT_SERVICE, user_services)?
+        }
+    };
+    println!("Using service: {}", service);
+    Ok(service)
+}
+
+fn get_output_settings(args: &Opts, config_file: Option<&ConfigFile>) -> Result<OutputSettings> {
+    Ok(OutputSettings {
+        format: args
+            .format
+            .or(config_file.and_then(|file| file.output_format))
+            .unwrap_or_default(),
+        bitrate: args.bitrate.or(config_file.and_then(|file| file.bitrate)),
+        vbr_quality: args
+            .vbr_quality
+            .or(config_file.and_then(|file| file.vbr_quality)),
+        flac_compression_level: args
+            .compression_level
+            .or(config_file.and_then(|file| file.flac_compression_level)),
+        path_template: config_file
+            .and_then(|file| file.path_template.as_ref())
+            .map(|template| template.parse())
+            .transpose()?
+            .unwrap_or_default(),
+        sanitization: config_file
+            .and_then(|file| file.sanitization)
+            .unwrap_or_default(),
+    })
+}
+
+fn run_gui(
+    dir: &Path,
+    service: Service,
+    sink_type: SinkType,
+    pause_mode: PauseMode,
+    offset_mode: OffsetMode,
+    output_settings: OutputSettings,
+) {
+    let app = StriputaryGui::new(
+        dir,
+        service,
+        sink_type,
+        pause_mode,
+        offset_mode,
+        output_settings,
+    );
     let native_options = eframe::NativeOptions::default();
     eframe::run_native("striputary", native_options, Box::new(|_| Box::new(app)));
 }
@@ -0,0 +1,25 @@
+use std::str::FromStr;
+
+use serde::Deserialize;
+use serde::Serialize;
+
+use crate::util::from_yaml_str;
+
+#[derive(Copy, Clone, Debug, Default, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OffsetMode {
+    /// Use the same offset for all cuts.
+    #[default]
+    Global,
+    /// Start from the global offset and refine the offset of each
+    /// cut locally. Helps with drift in long sessions.
+    PerCut,
+}
+
+impl FromStr for OffsetMode {
+    type Err = serde_yaml::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        from_yaml_str(s)
+    }
+}
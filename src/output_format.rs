@@ -0,0 +1,167 @@
+use std::str::FromStr;
+
+use serde::Deserialize;
+use serde::Serialize;
+
+use crate::config;
+use crate::path_template::PathTemplate;
+use crate::path_template::Sanitization;
+use crate::util::from_yaml_str;
+
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OutputFormat {
+    #[default]
+    Opus,
+    Flac,
+    Mp3,
+    Vorbis,
+    Wav,
+}
+
+impl FromStr for OutputFormat {
+    type Err = serde_yaml::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        from_yaml_str(s)
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum MetadataTag {
+    Title,
+    Album,
+    Artist,
+    AlbumArtist,
+    TrackNumber,
+    DiscNumber,
+    Genre,
+    Date,
+    /// The MPRIS track id, which identifies the song within the media player
+    TrackId,
+}
+
+impl MetadataTag {
+    pub fn vorbis_comment_key(&self) -> &'static str {
+        match self {
+            Self::Title => "TITLE",
+            Self::Album => "ALBUM",
+            Self::Artist => "ARTIST",
+            Self::AlbumArtist => "ALBUMARTIST",
+            Self::TrackNumber => "TRACKNUMBER",
+            Self::DiscNumber => "DISCNUMBER",
+            Self::Genre => "GENRE",
+            Self::Date => "DATE",
+            Self::TrackId => "MPRIS_TRACKID",
+        }
+    }
+
+    pub fn riff_info_id(&self) -> Option<&'static [u8; 4]> {
+        match self {
+            Self::Title => Some(b"INAM"),
+            Self::Album => Some(b"IPRD"),
+            Self::Artist => Some(b"IART"),
+            Self::AlbumArtist => None,
+            Self::TrackNumber => Some(b"ITRK"),
+            Self::DiscNumber => None,
+            Self::Genre => Some(b"IGNR"),
+            Self::Date => Some(b"ICRD"),
+            Self::TrackId => None,
+        }
+    }
+}
+
+impl OutputFormat {
+    /// Lossless formats are encoded in-process, lossy formats require
+    /// ffmpeg.
+    pub fn can_encode_natively(&self) -> bool {
+        matches!(self, Self::Wav | Self::Flac)
+    }
+
+    pub fn extension(&self) -> &'static str {
+        match self {
+            Self::Opus => "opus",
+            Self::Flac => "flac",
+            Self::Mp3 => "mp3",
+            Self::Vorbis => "ogg",
+            Self::Wav => "wav",
+        }
+    }
+
+    fn ffmpeg_codec(&self) -> &'static str {
+        match self {
+            Self::Opus => "libopus",
+            Self::Flac => "flac",
+            Self::Mp3 => "libmp3lame",
+            Self::Vorbis => "libvorbis",
+            Self::Wav => "pcm_s16le",
+        }
+    }
+
+    /// The ffmpeg metadata key under which the tag is stored for this
+    /// format, or None if the container cannot hold the tag.
+    pub fn metadata_key(&self, tag: MetadataTag) -> Option<&'static str> {
+        match (self, tag) {
+            (_, MetadataTag::Title) => Some("title"),
+            (_, MetadataTag::Album) => Some("album"),
+            (_, MetadataTag::Artist) => Some("artist"),
+            (Self::Wav, MetadataTag::AlbumArtist) => None,
+            (Self::Mp3, MetadataTag::AlbumArtist) => Some("album_artist"),
+            (_, MetadataTag::AlbumArtist) => Some("albumartist"),
+            (_, MetadataTag::TrackNumber) => Some("track"),
+            (Self::Wav, MetadataTag::DiscNumber | MetadataTag::TrackId) => None,
+            (_, MetadataTag::DiscNumber) => Some("disc"),
+            (_, MetadataTag::Genre) => Some("genre"),
+            (_, MetadataTag::Date) => Some("date"),
+            (_, MetadataTag::TrackId) => Some("mpris_trackid"),
+        }
+    }
+}
+
+/// The output format along with its encoder settings.
+/// Settings that do not apply to the chosen format are ignored.
+#[derive(Clone, Debug, Default)]
+pub struct OutputSettings {
+    pub format: OutputFormat,
+    /// Bitrate in bits per second for lossy formats
+    pub bitrate: Option<i64>,
+    /// Quality for variable bitrate encoding of mp3 (0 to 9, lower is better)
+    /// and vorbis (-1 to 10, higher is better). Takes precedence over the bitrate.
+    pub vbr_quality: Option<f64>,
+    /// Compression level for flac (0 to 12)
+    pub flac_compression_level: Option<u32>,
+    pub path_template: PathTemplate,
+    pub sanitization: Sanitization,
+}
+
+impl OutputSettings {
+    pub fn extension(&self) -> &'static str {
+        self.format.extension()
+    }
+
+    pub fn get_ffmpeg_encoder_args(&self) -> Vec<String> {
+        let mut args = vec!["-c:a".into(), self.format.ffmpeg_codec().into()];
+        match self.format {
+            OutputFormat::Opus => {
+                args.extend(self.get_bitrate_args());
+            }
+            OutputFormat::Mp3 | OutputFormat::Vorbis => match self.vbr_quality {
+                Some(quality) => args.extend(["-q:a".into(), format!("{}", quality)]),
+                None => args.extend(self.get_bitrate_args()),
+            },
+            // Encoded natively, see can_encode_natively.
+            OutputFormat::Flac | OutputFormat::Wav => {}
+        }
+        if self.format == OutputFormat::Mp3 {
+            args.extend(["-id3v2_version".into(), "3".into()]);
+        }
+        args
+    }
+
+    fn get_bitrate_args(&self) -> [String; 2] {
+        [
+            "-b:a".into(),
+            format!("{}", self.bitrate.unwrap_or(config::BITRATE)),
+        ]
+    }
+}
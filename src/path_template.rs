@@ -0,0 +1,327 @@
+use std::path::Path;
+use std::path::PathBuf;
+use std::str::FromStr;
+
+use anyhow::anyhow;
+use anyhow::Result;
+use serde::Deserialize;
+use serde::Serialize;
+
+use crate::song::Song;
+use crate::util::from_yaml_str;
+
+pub static DEFAULT_PATH_TEMPLATE: &str = "{artist}/{album}/{track:02}_{title}.{ext}";
+
+/// Characters which are not allowed in file names on at least
+/// one of the common file systems.
+static INVALID_PATH_CHARS: &[char] = &['/', '\\', ':', '*', '?', '"', '<', '>', '|'];
+
+#[derive(Copy, Clone, Debug, Default, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Sanitization {
+    /// Remove all spaces and everything after the first comma.
+    #[default]
+    Compact,
+    /// Keep spaces, commas and unicode characters and only replace
+    /// characters that are invalid in file names.
+    Filesystem,
+}
+
+impl FromStr for Sanitization {
+    type Err = serde_yaml::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        from_yaml_str(s)
+    }
+}
+
+impl Sanitization {
+    pub fn sanitize(&self, s: &str) -> String {
+        let sanitized = match self {
+            Self::Compact => {
+                let first_item = s.split(',').next().unwrap();
+                first_item.replace(['/', ' '], "")
+            }
+            Self::Filesystem => s
+                .chars()
+                .map(|c| {
+                    if INVALID_PATH_CHARS.contains(&c) || c.is_control() {
+                        '_'
+                    } else {
+                        c
+                    }
+                })
+                .collect::<String>()
+                .trim_start()
+                .trim_end_matches(|c: char| c == '.' || c.is_whitespace())
+                .to_owned(),
+        };
+        if sanitized == "." || sanitized == ".." {
+            "_".into()
+        } else {
+            sanitized
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+enum Token {
+    Literal(String),
+    Field { name: Field, width: Option<usize> },
+}
+
+#[derive(Copy, Clone, Debug)]
+enum Field {
+    Artist,
+    AlbumArtist,
+    Album,
+    Title,
+    Track,
+    Disc,
+    Year,
+    Ext,
+    Num,
+}
+
+impl FromStr for Field {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        Ok(match s {
+            "artist" => Self::Artist,
+            "albumartist" => Self::AlbumArtist,
+            "album" => Self::Album,
+            "title" => Self::Title,
+            "track" => Self::Track,
+            "disc" => Self::Disc,
+            "year" => Self::Year,
+            "ext" => Self::Ext,
+            "num" => Self::Num,
+            _ => return Err(anyhow!("Unknown field in path template: {}", s)),
+        })
+    }
+}
+
+/// A template for the path of the cut files, relative to the music
+/// directory. Fields are written in braces, such as `{album}`.
+/// Numeric fields can be zero-padded to a given width, for example `{track:02}`.
+#[derive(Clone, Debug)]
+pub struct PathTemplate {
+    tokens: Vec<Token>,
+}
+
+impl Default for PathTemplate {
+    fn default() -> Self {
+        DEFAULT_PATH_TEMPLATE.parse().unwrap()
+    }
+}
+
+impl FromStr for PathTemplate {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let mut tokens = vec![];
+        let mut rest = s;
+        while let Some(start) = rest.find('{') {
+            if start > 0 {
+                tokens.push(Token::Literal(rest[..start].into()));
+            }
+            let end = rest[start..]
+                .find('}')
+                .ok_or_else(|| anyhow!("Unclosed brace in path template: {}", s))?
+                + start;
+            tokens.push(parse_field(&rest[start + 1..end])?);
+            rest = &rest[end + 1..];
+        }
+        if !rest.is_empty() {
+            tokens.push(Token::Literal(rest.into()));
+        }
+        Ok(Self { tokens })
+    }
+}
+
+fn parse_field(s: &str) -> Result<Token> {
+    let (name, width) = match s.split_once(':') {
+        Some((name, width)) => (
+            name,
+            Some(
+                width
+                    .parse()
+                    .map_err(|_| anyhow!("Invalid width in path template: {}", width))?,
+            ),
+        ),
+        None => (s, None),
+    };
+    Ok(Token::Field {
+        name: name.parse()?,
+        width,
+    })
+}
+
+impl PathTemplate {
+    pub fn get_target_file(
+        &self,
+        music_dir: &Path,
+        song: &Song,
+        num_in_recording: usize,
+        extension: &str,
+        sanitization: Sanitization,
+    ) -> PathBuf {
+        let path: String = self
+            .tokens
+            .iter()
+            .map(|token| match token {
+                Token::Literal(literal) => literal.clone(),
+                Token::Field { name, width } => {
+                    let value =
+                        get_field_value(song, *name, num_in_recording, extension, sanitization);
+                    match (value, width) {
+                        (FieldValue::Number(number), Some(width)) => {
+                            format!("{:0width$}", number, width = width)
+                        }
+                        (FieldValue::Number(number), None) => number.to_string(),
+                        (FieldValue::Text(text), _) => sanitization.sanitize(&text),
+                    }
+                }
+            })
+            .collect();
+        music_dir.join(path)
+    }
+}
+
+enum FieldValue {
+    Text(String),
+    Number(i64),
+}
+
+/// Sanitizes the text and falls back to the default if
+/// nothing is left of it.
+pub fn sanitize_or_default(
+    s: &Option<String>,
+    default: &str,
+    sanitization: Sanitization,
+) -> String {
+    s.as_ref()
+        .map(|s| sanitization.sanitize(s))
+        .filter(|s| !s.is_empty())
+        .unwrap_or_else(|| default.into())
+}
+
+fn get_field_value(
+    song: &Song,
+    field: Field,
+    num_in_recording: usize,
+    extension: &str,
+    sanitization: Sanitization,
+) -> FieldValue {
+    let text_or_default = |s: &Option<String>, default: &str| {
+        FieldValue::Text(sanitize_or_default(s, default, sanitization))
+    };
+    match field {
+        Field::Artist => text_or_default(&song.artist, "unknown_artist"),
+        Field::AlbumArtist => text_or_default(&song.album_artist().cloned(), "unknown_artist"),
+        Field::Album => text_or_default(&song.album, "unknown_album"),
+        Field::Title => text_or_default(&song.title, "unknown_title"),
+        Field::Track => match song.track_number {
+            Some(track_number) => FieldValue::Number(track_number),
+            None => FieldValue::Text(format!("recording_{}", num_in_recording)),
+        },
+        Field::Disc => FieldValue::Number(song.disc_number.unwrap_or(1)),
+        Field::Year => text_or_default(&song.year().map(|year| year.into()), "unknown_year"),
+        Field::Ext => FieldValue::Text(extension.into()),
+        Field::Num => FieldValue::Number(num_in_recording as i64),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+    use std::path::PathBuf;
+
+    use super::PathTemplate;
+    use super::Sanitization;
+    use crate::song::Song;
+
+    fn get_song() -> Song {
+        Song {
+            artist: Some("Some Artist, Other Artist".into()),
+            album: Some("Album: Live/Remastered".into()),
+            title: Some("Title?".into()),
+            track_number: Some(3),
+            ..Song::default()
+        }
+    }
+
+    fn get_target_file(template: &str, song: &Song, sanitization: Sanitization) -> PathBuf {
+        let template: PathTemplate = template.parse().unwrap();
+        template.get_target_file(Path::new("music"), song, 7, "flac", sanitization)
+    }
+
+    #[test]
+    fn default_template() {
+        assert_eq!(
+            get_target_file(
+                super::DEFAULT_PATH_TEMPLATE,
+                &get_song(),
+                Sanitization::Compact
+            ),
+            Path::new("music/SomeArtist/Album:LiveRemastered/03_Title?.flac")
+        );
+        assert_eq!(
+            get_target_file(
+                super::DEFAULT_PATH_TEMPLATE,
+                &get_song(),
+                Sanitization::Filesystem
+            ),
+            Path::new("music/Some Artist, Other Artist/Album_ Live_Remastered/03_Title_.flac")
+        );
+    }
+
+    #[test]
+    fn missing_fields_are_replaced() {
+        let song = Song::default();
+        assert_eq!(
+            get_target_file(
+                "{artist}/{track}_{num:03}_{disc}",
+                &song,
+                Sanitization::Compact
+            ),
+            Path::new("music/unknown_artist/recording_7_007_1")
+        );
+    }
+
+    #[test]
+    fn default_is_used_if_nothing_is_left_after_sanitization() {
+        let song = Song {
+            artist: Some(" / ".into()),
+            title: Some("...".into()),
+            ..Song::default()
+        };
+        assert_eq!(
+            get_target_file("{artist}", &song, Sanitization::Compact),
+            Path::new("music/unknown_artist")
+        );
+        assert_eq!(
+            get_target_file("{title}", &song, Sanitization::Filesystem),
+            Path::new("music/unknown_title")
+        );
+    }
+
+    #[test]
+    fn invalid_templates_are_rejected() {
+        assert!("{artist".parse::<PathTemplate>().is_err());
+        assert!("{composer}".parse::<PathTemplate>().is_err());
+        assert!("{track:wide}".parse::<PathTemplate>().is_err());
+    }
+
+    #[test]
+    fn sanitization() {
+        assert_eq!(Sanitization::Compact.sanitize("A B, C"), "AB");
+        assert_eq!(Sanitization::Compact.sanitize("AC/DC"), "ACDC");
+        assert_eq!(Sanitization::Filesystem.sanitize("A B, C"), "A B, C");
+        assert_eq!(Sanitization::Filesystem.sanitize("AC/DC"), "AC_DC");
+        assert_eq!(Sanitization::Filesystem.sanitize(" What? ."), "What_");
+        assert_eq!(Sanitization::Filesystem.sanitize(".."), "");
+        assert_eq!(Sanitization::Compact.sanitize(".."), "_");
+    }
+}
@@ -0,0 +1,27 @@
+use std::str::FromStr;
+
+use serde::Deserialize;
+use serde::Serialize;
+
+use crate::util::from_yaml_str;
+
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PauseMode {
+    /// Pausing the playback ends the session.
+    #[default]
+    EndSession,
+    /// Keep recording while the playback is paused. Every pause
+    /// starts a new segment of the session, whose offset is
+    /// determined separately. The session only ends once the
+    /// playback is stopped or paused for too long.
+    Split,
+}
+
+impl FromStr for PauseMode {
+    type Err = serde_yaml::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        from_yaml_str(s)
+    }
+}
@@ -0,0 +1,55 @@
+use serde::Deserialize;
+use serde::Serialize;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PlaybackStatus {
+    Playing,
+    Paused,
+    Stopped,
+}
+
+impl PlaybackStatus {
+    /// Parses the PlaybackStatus property of the MPRIS player interface.
+    pub fn from_mpris(status: &str) -> Option<Self> {
+        match status {
+            "Playing" => Some(Self::Playing),
+            "Paused" => Some(Self::Paused),
+            "Stopped" => Some(Self::Stopped),
+            _ => None,
+        }
+    }
+}
+
+/// Something that happened in the media player while recording.
+/// All times are in seconds since the start of the recording, as
+/// measured when the corresponding MPRIS signal was received.
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum PlaybackEvent {
+    /// The song with the given index in the session started playing.
+    SongChanged {
+        time: f64,
+        song: usize,
+    },
+    StatusChanged {
+        time: f64,
+        status: PlaybackStatus,
+    },
+    /// The player jumped to the given position (in seconds)
+    /// within the current song.
+    Seeked {
+        time: f64,
+        position: f64,
+    },
+}
+
+impl PlaybackEvent {
+    pub fn time(&self) -> f64 {
+        match self {
+            Self::SongChanged { time, .. } => *time,
+            Self::StatusChanged { time, .. } => *time,
+            Self::Seeked { time, .. } => *time,
+        }
+    }
+}
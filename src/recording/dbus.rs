@@ -1,50 +1,93 @@
 use std::collections::HashMap;
-use std::process::Command;
+use std::time::Duration;
+use std::time::Instant;
 
-use anyhow::anyhow;
-use anyhow::Context;
 use anyhow::Result;
+use dbus::arg::AppendAll;
+use dbus::arg::PropMap;
+use dbus::arg::ReadAll;
 use dbus::arg::RefArg;
+use dbus::arg::Variant;
 use dbus::ffidisp::stdintf::org_freedesktop_dbus::PropertiesPropertiesChanged as PC;
 use dbus::ffidisp::Connection;
 use dbus::message::SignalArgs;
+use dbus::strings::BusName;
+use dbus::Message;
 
-use crate::recording::recording_status::RecordingExitStatus;
-use crate::recording::recording_status::RecordingStatus;
+use crate::config::DBUS_METHOD_CALL_TIMEOUT_MS;
+use crate::errors::DbusError;
+use crate::playback_event::PlaybackEvent;
+use crate::playback_event::PlaybackStatus;
 use crate::recording_session::RecordingSession;
 use crate::service_config::ServiceConfig;
 use crate::song::Song;
 
+static DBUS_BUS_NAME: &str = "org.freedesktop.DBus";
+static DBUS_OBJECT_PATH: &str = "/org/freedesktop/DBus";
+static PROPERTIES_INTERFACE: &str = "org.freedesktop.DBus.Properties";
+static MPRIS_OBJECT_PATH: &str = "/org/mpris/MediaPlayer2";
+static MPRIS_PLAYER_INTERFACE: &str = "org.mpris.MediaPlayer2.Player";
+
 type MetadataDict<'a> = HashMap<&'a str, Box<dyn RefArg + 'a>>;
 
 /// Collect dbus information on the songs.
-/// We could collect the dbus timestamps but they are basically useless
-/// for cutting the songs since they fluctuate way too much to be precise.
+/// The time at which every signal is received is stored along with it.
+/// These timestamps fluctuate way too much to cut the songs precisely,
+/// but they tell us when the playback was not contiguous.
 pub fn collect_dbus_info(
     session: &mut RecordingSession,
     service_config: &ServiceConfig,
-) -> Result<RecordingStatus> {
-    let c = Connection::new_session().unwrap();
+    record_start_time: &Instant,
+) -> Result<()> {
+    let c = connect()?;
     // Add a match for this signal
-    let bus_name = service_config.dbus_bus_name.clone();
-    let mstr = PC::match_str(Some(&bus_name.into()), None);
-    c.add_match(&mstr).unwrap();
+    let bus_name = BusName::new(&service_config.dbus_bus_name[..])
+        .map_err(|_| DbusError::InvalidBusName(service_config.dbus_bus_name.clone()))?;
+    let mstr = PC::match_str(Some(&bus_name), None);
+    c.add_match(&mstr).map_err(DbusError::Connection)?;
+    c.add_match(&get_seeked_match_str(&bus_name))
+        .map_err(DbusError::Connection)?;
 
     // Wait for the signal to arrive.
     for msg in c.incoming(100) {
+        let time = record_start_time.elapsed().as_secs_f64();
         if let Some(pc) = PC::from_message(&msg) {
-            return handle_dbus_properties_changed_signal(session, pc);
+            return handle_dbus_properties_changed_signal(session, pc, time);
+        }
+        if let Some(position) = get_seeked_position(&msg) {
+            session
+                .playback_events
+                .push(PlaybackEvent::Seeked { time, position });
+            return session.save();
         }
     }
-    Ok(RecordingStatus::Running)
+    Ok(())
 }
 
 pub fn handle_dbus_properties_changed_signal(
     session: &mut RecordingSession,
     properties: PC,
-) -> Result<RecordingStatus> {
-    let playback_stopped = is_playback_stopped(&properties);
-    if !playback_stopped {
+    time: f64,
+) -> Result<()> {
+    let status = get_playback_status(&properties);
+    if let Some(status) = status {
+        // Like the metadata, the status is repeated in many messages.
+        let last_status = session
+            .playback_events
+            .iter()
+            .rev()
+            .find_map(|event| match event {
+                PlaybackEvent::StatusChanged { status, .. } => Some(*status),
+                _ => None,
+            });
+        if last_status != Some(status) {
+            session
+                .playback_events
+                .push(PlaybackEvent::StatusChanged { time, status });
+            session.save()?;
+        }
+    }
+    if status != Some(PlaybackStatus::Paused) {
         let song = get_song_from_dbus_properties(properties);
         // We get multiple dbus messages on every song change for every property that changes.
         // Find out whether the song actually changed (or whether we havent recorded anything so far)
@@ -53,74 +96,75 @@ pub fn handle_dbus_properties_changed_signal(
             if session.songs.is_empty() || last_song.unwrap() != &song {
                 println!("Now recording song: {}", song);
                 session.songs.push(song);
+                session.playback_events.push(PlaybackEvent::SongChanged {
+                    time,
+                    song: session.songs.len() - 1,
+                });
                 session.save()?;
             }
         }
     }
-    match playback_stopped {
-        false => Ok(RecordingStatus::Running),
-        true => Ok(RecordingStatus::Finished(
-            RecordingExitStatus::FinishedOrInterrupted,
-        )),
-    }
+    Ok(())
 }
 
-fn is_playback_stopped(properties: &PC) -> bool {
-    let has_playback_status_entry = properties.changed_properties.contains_key("PlaybackStatus");
-    if has_playback_status_entry {
-        let variant = &properties.changed_properties["PlaybackStatus"];
-        (variant.0).as_str().unwrap() == "Paused"
-    } else {
-        false
-    }
+fn get_playback_status(properties: &PC) -> Option<PlaybackStatus> {
+    properties
+        .changed_properties
+        .get("PlaybackStatus")
+        .and_then(|variant| variant.0.as_str())
+        .and_then(PlaybackStatus::from_mpris)
 }
 
-fn get_song_length(metadata: &MetadataDict) -> f64 {
-    let val = &metadata["mpris:length"];
-    let length_microseconds = val
-        .as_u64()
-        .or(val.as_i64().map(|x| x as u64))
-        .unwrap_or_else(|| {
-            val.as_str()
-                .expect("Failed to parse song length as string")
-                .parse()
-                .expect("Failed to parse song length string as integer")
-        });
-    (length_microseconds as f64) * 1e-6
+fn get_seeked_match_str(bus_name: &BusName) -> String {
+    format!(
+        "type='signal',sender='{}',path='{}',interface='{}',member='Seeked'",
+        bus_name, MPRIS_OBJECT_PATH, MPRIS_PLAYER_INTERFACE
+    )
 }
 
-fn get_song_artist(metadata: &MetadataDict) -> Option<String> {
-    // I want to thank what is probably a combination of spotify and the MediaPlayer2 specification for this wonderful piece of art. Note that spotify doesn't actually send a list of artists, but just the first artist in a nested list which is just great.
-    Some(
-        metadata["xesam:artist"]
-            .as_iter()
-            .unwrap()
-            .next()
-            .unwrap()
-            .as_iter()
-            .unwrap()
-            .next()
-            .unwrap()
-            .as_str()
-            .unwrap()
-            .to_string(),
-    )
+/// Returns the new position (in seconds) if the message
+/// is a Seeked signal of the MPRIS player interface.
+fn get_seeked_position(msg: &Message) -> Option<f64> {
+    if &*msg.interface()? != MPRIS_PLAYER_INTERFACE || &*msg.member()? != "Seeked" {
+        return None;
+    }
+    let position_microseconds: i64 = msg.read1().ok()?;
+    Some(position_microseconds as f64 * 1e-6)
 }
 
-fn get_song_album(metadata: &MetadataDict) -> Option<String> {
+/// Reads a list of strings. Media players differ in whether they
+/// send a single string or a (possibly nested) list of strings,
+/// so this accepts any of these.
+fn get_strings(value: &dyn RefArg) -> Vec<String> {
+    if let Some(s) = value.as_str() {
+        vec![s.to_string()]
+    } else if let Some(iter) = value.as_iter() {
+        iter.flat_map(get_strings).collect()
+    } else {
+        vec![]
+    }
+}
+
+fn get_string(metadata: &MetadataDict, key: &str) -> Option<String> {
     metadata
-        .get("xesam:album")
-        .map(|album| album.as_str().unwrap().to_string())
+        .get(key)
+        .and_then(|value| get_strings(value).into_iter().next())
+        .filter(|s| !s.is_empty())
 }
 
-fn get_song_title(metadata: &MetadataDict) -> Option<String> {
-    Some(metadata["xesam:title"].as_str().unwrap().to_string())
+fn get_integer(metadata: &MetadataDict, key: &str) -> Option<i64> {
+    let value = metadata.get(key)?;
+    value
+        .as_i64()
+        .or_else(|| value.as_u64().map(|x| x as i64))
+        .or_else(|| value.as_str().and_then(|s| s.parse().ok()))
 }
 
-fn get_song_track_number(metadata: &MetadataDict) -> Option<i64> {
-    metadata
-        .get("xesam:trackNumber")
-        .map(|track_number| track_number.as_i64().unwrap())
+/// Returns the song length in seconds, or zero
+/// if the length is missing or malformed.
+fn get_song_length(metadata: &MetadataDict) -> f64 {
+    let length_microseconds = get_integer(metadata, "mpris:length").unwrap_or(0);
+    (length_microseconds as f64) * 1e-6
 }
 
 /// This filters certain malformed entries that
@@ -134,79 +178,170 @@ fn is_valid_song(song: &Song) -> bool {
 fn get_song_from_dbus_properties(properties: PC) -> Option<Song> {
     let metadata = &properties.changed_properties.get("Metadata")?.0;
 
-    let mut iter = metadata.as_iter().unwrap();
-    let mut dict = HashMap::<&str, Box<dyn RefArg>>::new();
-    while let Some(key) = iter.next() {
-        let value = iter.next().unwrap();
-        dict.insert(key.as_str().unwrap(), Box::new(value));
+    let mut iter = metadata.as_iter()?;
+    let mut dict = MetadataDict::new();
+    while let (Some(key), Some(value)) = (iter.next(), iter.next()) {
+        if let Some(key) = key.as_str() {
+            dict.insert(key, Box::new(value));
+        }
     }
-    return Some(Song {
-        artist: get_song_artist(&dict),
-        album: get_song_album(&dict),
-        title: get_song_title(&dict),
-        track_number: get_song_track_number(&dict),
+    let artists = dict
+        .get("xesam:artist")
+        .map(|artists| get_strings(artists))
+        .unwrap_or_default();
+    Some(Song {
+        artist: artists.first().cloned(),
+        artists,
+        album_artists: dict
+            .get("xesam:albumArtist")
+            .map(|artists| get_strings(artists))
+            .unwrap_or_default(),
+        album: get_string(&dict, "xesam:album"),
+        title: get_string(&dict, "xesam:title"),
+        track_number: get_integer(&dict, "xesam:trackNumber"),
+        disc_number: get_integer(&dict, "xesam:discNumber"),
+        genres: dict
+            .get("xesam:genre")
+            .map(|genres| get_strings(genres))
+            .unwrap_or_default(),
+        content_created: get_string(&dict, "xesam:contentCreated"),
+        art_url: get_string(&dict, "mpris:artUrl"),
+        track_id: get_string(&dict, "mpris:trackid"),
         length: get_song_length(&dict),
     })
-    .filter(|song| is_valid_song(&song));
+    .filter(is_valid_song)
+}
+
+fn connect() -> Result<Connection, DbusError> {
+    Connection::new_session().map_err(DbusError::Connection)
+}
+
+fn method_call<R: ReadAll, A: AppendAll>(
+    connection: &Connection,
+    destination: &str,
+    path: &str,
+    interface: &str,
+    method: &str,
+    args: A,
+) -> Result<R, DbusError> {
+    let bus_name =
+        BusName::new(destination).map_err(|_| DbusError::InvalidBusName(destination.into()))?;
+    connection
+        .with_path(bus_name, path, DBUS_METHOD_CALL_TIMEOUT_MS)
+        .method_call(interface, method, args)
+        .map_err(|error| DbusError::MethodCall {
+            destination: destination.into(),
+            method: method.into(),
+            error,
+        })
+}
+
+fn player_method_call<A: AppendAll>(
+    service_config: &ServiceConfig,
+    method: &str,
+    args: A,
+) -> Result<(), DbusError> {
+    method_call(
+        &connect()?,
+        &service_config.dbus_bus_name,
+        MPRIS_OBJECT_PATH,
+        MPRIS_PLAYER_INTERFACE,
+        method,
+        args,
+    )
 }
 
 pub fn dbus_set_playback_status_command(
     service_config: &ServiceConfig,
     command: &str,
-) -> Result<()> {
-    Command::new("dbus-send")
-        .arg("--print-reply")
-        .arg(format!("--dest={}", &service_config.dbus_bus_name))
-        .arg("/org/mpris/MediaPlayer2")
-        .arg(format!("org.mpris.MediaPlayer2.Player.{}", command))
-        .output()
-        .context("Failed to send dbus command to control playback")
-        .map(|_| ()) // We do not need the output, let's not suggest that it is useful for the caller
+) -> Result<(), DbusError> {
+    player_method_call(service_config, command, ())
 }
 
-pub fn previous_song(service_config: &ServiceConfig) -> Result<()> {
+pub fn previous_song(service_config: &ServiceConfig) -> Result<(), DbusError> {
     dbus_set_playback_status_command(service_config, "Previous")
 }
 
-pub fn next_song(service_config: &ServiceConfig) -> Result<()> {
+pub fn next_song(service_config: &ServiceConfig) -> Result<(), DbusError> {
     dbus_set_playback_status_command(service_config, "Next")
 }
 
-pub fn start_playback(service_config: &ServiceConfig) -> Result<()> {
+pub fn start_playback(service_config: &ServiceConfig) -> Result<(), DbusError> {
     dbus_set_playback_status_command(service_config, "Play")
 }
 
-pub fn stop_playback(service_config: &ServiceConfig) -> Result<()> {
+pub fn stop_playback(service_config: &ServiceConfig) -> Result<(), DbusError> {
     dbus_set_playback_status_command(service_config, "Pause")
 }
 
+/// Returns the MPRIS track id of the song that is currently playing.
+fn get_current_track_id(service_config: &ServiceConfig) -> Result<String, DbusError> {
+    let (metadata,): (Variant<PropMap>,) = method_call(
+        &connect()?,
+        &service_config.dbus_bus_name,
+        MPRIS_OBJECT_PATH,
+        PROPERTIES_INTERFACE,
+        "Get",
+        (MPRIS_PLAYER_INTERFACE, "Metadata"),
+    )?;
+    metadata
+        .0
+        .get("mpris:trackid")
+        .and_then(|track_id| track_id.0.as_str())
+        .map(|track_id| track_id.to_string())
+        .ok_or_else(|| DbusError::MissingTrackId(service_config.dbus_bus_name.clone()))
+}
+
+/// Seeks to the given position within the song that is currently playing.
+pub fn set_position(service_config: &ServiceConfig, position: Duration) -> Result<(), DbusError> {
+    let track_id = get_current_track_id(service_config)?;
+    let track_id = dbus::Path::new(track_id)
+        .map_err(|_| DbusError::MissingTrackId(service_config.dbus_bus_name.clone()))?;
+    player_method_call(
+        service_config,
+        "SetPosition",
+        (track_id, position.as_micros() as i64),
+    )
+}
+
+/// Seeks to the beginning of the current song.
+pub fn rewind(service_config: &ServiceConfig) -> Result<(), DbusError> {
+    set_position(service_config, Duration::ZERO)
+}
+
+/// Returns all bus names on the session bus which start with the given prefix.
+pub fn get_matching_services(service_base_name: &str) -> Result<Vec<String>, DbusError> {
+    let (names,): (Vec<String>,) = method_call(
+        &connect()?,
+        DBUS_BUS_NAME,
+        DBUS_OBJECT_PATH,
+        DBUS_BUS_NAME,
+        "ListNames",
+        (),
+    )?;
+    let mut names: Vec<_> = names
+        .into_iter()
+        .filter(|name| name.starts_with(service_base_name))
+        .collect();
+    names.sort();
+    Ok(names)
+}
+
 /// For some mpris services, the name is not constant
 /// but changes depending on the instance id running.
 /// Here, we get a list of all available services
-/// and find the matching one. Returns an error
+/// and find the matching one. A service with exactly
+/// the given name takes precedence. Otherwise, returns an error
 /// if there are multiple matches.
-pub fn get_instance_of_service(service_base_name: &str) -> Result<String> {
-    let out = Command::new("qdbus")
-        .arg("--session")
-        .output()
-        .context("Failed to get list of services with qdbus")?;
-    let out = String::from_utf8(out.stdout)?;
-    let matching_lines: Vec<_> = out
-        .lines()
-        .map(|line| line.trim())
-        .filter(|line| line.starts_with(service_base_name))
-        .collect();
-    if matching_lines.len() > 1 {
-        Err(anyhow!(
-            "Found multiple dbus services that match the service configuration: {}",
-            matching_lines.join(", ")
-        ))
-    } else if matching_lines.is_empty() {
-        Err(anyhow!(
-            "Found no matching dbus service for base name: {}",
-            service_base_name
-        ))
+pub fn get_instance_of_service(service_base_name: &str) -> Result<String, DbusError> {
+    let mut matching_names = get_matching_services(service_base_name)?;
+    if matching_names.iter().any(|name| name == service_base_name) {
+        Ok(service_base_name.into())
+    } else if matching_names.len() > 1 {
+        Err(DbusError::MultipleMatchingServices(matching_names))
     } else {
-        Ok(matching_lines[0].into())
+        matching_names
+            .pop()
+            .ok_or_else(|| DbusError::NoMatchingService(service_base_name.into()))
     }
 }
@@ -0,0 +1,88 @@
+//! Handling of SIGINT. While recording, an interrupt ends the recording
+//! cleanly, so that the changes to the audio server are undone.
+//! Otherwise (or on a second interrupt), the process exits immediately.
+
+use std::process;
+use std::sync::atomic::AtomicBool;
+use std::sync::atomic::AtomicUsize;
+use std::sync::atomic::Ordering;
+use std::thread;
+use std::time::Duration;
+use std::time::Instant;
+
+use anyhow::Context;
+use anyhow::Result;
+
+static INTERRUPTED: AtomicBool = AtomicBool::new(false);
+static NUM_ACTIVE_RECORDINGS: AtomicUsize = AtomicUsize::new(0);
+
+/// The exit code of a process that was terminated by SIGINT.
+const SIGINT_EXIT_CODE: i32 = 130;
+
+pub fn install_handler() -> Result<()> {
+    ctrlc::set_handler(|| {
+        let was_interrupted = INTERRUPTED.swap(true, Ordering::SeqCst);
+        if was_interrupted || NUM_ACTIVE_RECORDINGS.load(Ordering::SeqCst) == 0 {
+            process::exit(SIGINT_EXIT_CODE);
+        }
+        println!("Interrupted. Stopping recording.");
+    })
+    .context("Failed to install interrupt handler")
+}
+
+pub fn is_interrupted() -> bool {
+    INTERRUPTED.load(Ordering::SeqCst)
+}
+
+/// Like thread::sleep, but returns early on an interrupt.
+pub fn sleep(duration: Duration) {
+    let start = Instant::now();
+    while !is_interrupted() {
+        let elapsed = start.elapsed();
+        if elapsed >= duration {
+            break;
+        }
+        thread::sleep((duration - elapsed).min(Duration::from_millis(100)));
+    }
+}
+
+/// Marks a recording as active for as long as the guard lives.
+/// An interrupt only ends the recordings that are active while it
+/// happens, so it is forgotten once the last of them has ended.
+pub struct RecordingGuard;
+
+impl RecordingGuard {
+    pub fn register() -> Self {
+        NUM_ACTIVE_RECORDINGS.fetch_add(1, Ordering::SeqCst);
+        Self
+    }
+}
+
+impl Drop for RecordingGuard {
+    fn drop(&mut self) {
+        let num_active_before = NUM_ACTIVE_RECORDINGS.fetch_sub(1, Ordering::SeqCst);
+        if num_active_before == 1 {
+            INTERRUPTED.store(false, Ordering::SeqCst);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::Ordering;
+
+    use super::is_interrupted;
+    use super::RecordingGuard;
+    use super::INTERRUPTED;
+
+    #[test]
+    fn interrupt_is_forgotten_after_last_recording_ends() {
+        let first = RecordingGuard::register();
+        let second = RecordingGuard::register();
+        INTERRUPTED.store(true, Ordering::SeqCst);
+        drop(first);
+        assert!(is_interrupted());
+        drop(second);
+        assert!(!is_interrupted());
+    }
+}
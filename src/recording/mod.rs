@@ -1,6 +1,9 @@
 pub mod dbus;
-mod recorder;
-mod recording_status;
+pub mod interrupt;
+mod pactl;
+mod pulse_native;
+pub mod recorder;
+pub mod recording_status;
 mod recording_thread;
-mod recording_thread_handle;
+pub mod recording_thread_handle;
 pub mod recording_thread_handle_status;
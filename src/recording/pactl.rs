@@ -0,0 +1,211 @@
+//! Fallback implementation of the audio server which runs pactl and parec.
+
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
+use std::path::PathBuf;
+use std::process::Command;
+use std::thread::JoinHandle;
+use std::thread::{self};
+use std::time::Instant;
+
+use anyhow::anyhow;
+use anyhow::Context;
+use anyhow::Result;
+use hound::WavSpec;
+use regex::Regex;
+use subprocess::Exec;
+use subprocess::Popen;
+use subprocess::Redirection;
+
+use super::recorder::AudioServer;
+use super::recorder::Sink;
+use super::recorder::SinkInput;
+use crate::config::BUFFER_FLUSH_INTERVAL;
+use crate::config::RECORDING_NUM_CHANNELS;
+use crate::config::RECORDING_SAMPLE_RATE;
+use crate::wav::SegmentedWavWriter;
+
+struct Recording {
+    parec: Popen,
+    thread: JoinHandle<Result<Vec<PathBuf>>>,
+}
+
+#[derive(Default)]
+pub struct PactlServer {
+    recording: Option<Recording>,
+}
+
+/// Runs the command and returns its standard output,
+/// failing if the command does not exit successfully.
+fn run_command(command: &mut Command) -> Result<String> {
+    let output = command.output().context(format!(
+        "Failed to execute {:?} - is it installed?",
+        command
+    ))?;
+    if !output.status.success() {
+        return Err(anyhow!(
+            "{:?} failed: {}",
+            command,
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+fn pactl<const N: usize>(args: [&str; N]) -> Result<String> {
+    run_command(Command::new("pactl").args(args))
+}
+
+fn parse_index(s: &str) -> Result<u32> {
+    s.trim()
+        .parse::<u32>()
+        .context(format!("Invalid index in pactl output: {}", s))
+}
+
+impl AudioServer for PactlServer {
+    fn list_sink_inputs(&mut self) -> Result<Vec<SinkInput>> {
+        let stdout = pactl(["list", "sink-inputs"])?;
+        let index_re = Regex::new("^Sink Input #([0-9]*)").unwrap();
+        let sink_re = Regex::new("^\\s*Sink: ([0-9]*)$").unwrap();
+        let property_re = Regex::new("^\\s*([a-zA-Z0-9_.]+) = \"(.*)\"$").unwrap();
+        let mut sink_inputs: Vec<SinkInput> = vec![];
+        for line in stdout.lines() {
+            if let Some(capture) = index_re.captures(line) {
+                sink_inputs.push(SinkInput {
+                    index: parse_index(&capture[1])?,
+                    sink: 0,
+                    properties: HashMap::new(),
+                });
+            } else if let (Some(capture), Some(sink_input)) =
+                (sink_re.captures(line), sink_inputs.last_mut())
+            {
+                sink_input.sink = parse_index(&capture[1])?;
+            } else if let (Some(capture), Some(sink_input)) =
+                (property_re.captures(line), sink_inputs.last_mut())
+            {
+                sink_input
+                    .properties
+                    .insert(capture[1].to_string(), capture[2].to_string());
+            }
+        }
+        Ok(sink_inputs)
+    }
+
+    fn get_default_sink(&mut self) -> Result<String> {
+        Ok(pactl(["get-default-sink"])?.trim().to_string())
+    }
+
+    fn list_sinks(&mut self) -> Result<Vec<Sink>> {
+        let stdout = pactl(["list", "sinks"])?;
+        let mut sinks: Vec<Sink> = vec![];
+        for line in stdout.lines().map(|line| line.trim()) {
+            if let Some(index) = line.strip_prefix("Sink #") {
+                sinks.push(Sink {
+                    index: parse_index(index)?,
+                    name: String::new(),
+                    owner_module: None,
+                });
+            } else if let Some(sink) = sinks.last_mut() {
+                if let Some(name) = line.strip_prefix("Name:") {
+                    sink.name = name.trim().to_string();
+                } else if let Some(module) = line.strip_prefix("Owner Module:") {
+                    // Sinks which were not created by a module show "n/a" here.
+                    sink.owner_module = parse_index(module).ok();
+                }
+            }
+        }
+        Ok(sinks)
+    }
+
+    fn load_module(&mut self, name: &str, arguments: &str) -> Result<u32> {
+        let mut command = Command::new("pactl");
+        command
+            .arg("load-module")
+            .arg(name)
+            .args(arguments.split_whitespace());
+        parse_index(&run_command(&mut command)?)
+    }
+
+    fn unload_module(&mut self, index: u32) -> Result<()> {
+        pactl(["unload-module", &index.to_string()]).map(|_| ())
+    }
+
+    fn move_sink_input(&mut self, index: u32, sink_name: &str) -> Result<()> {
+        pactl(["move-sink-input", &index.to_string(), sink_name]).map(|_| ())
+    }
+
+    /// Restarting parec would leave gaps in the recording, so parec
+    /// writes raw samples to a pipe and we split them into segments.
+    fn start_recording(&mut self, source_name: &str, buffer_dir: &Path) -> Result<()> {
+        if self.recording.is_some() {
+            return Err(anyhow!("Already recording"));
+        }
+        let spec = WavSpec {
+            channels: RECORDING_NUM_CHANNELS as u16,
+            sample_rate: RECORDING_SAMPLE_RATE,
+            bits_per_sample: 16,
+            sample_format: hound::SampleFormat::Int,
+        };
+        let writer = SegmentedWavWriter::new(buffer_dir, spec)
+            .context(format!("Failed to create buffer file in {:?}", buffer_dir))?;
+        let mut parec = Exec::cmd("parec")
+            .arg("-d")
+            .arg(source_name)
+            .arg("--raw")
+            .arg("--format=s16le")
+            .arg(format!("--channels={}", RECORDING_NUM_CHANNELS))
+            .arg(format!("--rate={}", RECORDING_SAMPLE_RATE))
+            .stdout(Redirection::Pipe)
+            .popen()
+            .context("Failed to execute record command - is parec installed?")?;
+        let stdout = parec
+            .stdout
+            .take()
+            .ok_or_else(|| anyhow!("No output pipe for parec"))?;
+        let thread = thread::spawn(move || record_pipe(stdout, writer));
+        self.recording = Some(Recording { parec, thread });
+        Ok(())
+    }
+
+    fn stop_recording(&mut self) -> Result<Vec<PathBuf>> {
+        match self.recording.take() {
+            Some(mut recording) => {
+                recording
+                    .parec
+                    .terminate()
+                    .context("Failed to terminate parec while recording")?;
+                recording.parec.wait()?;
+                recording
+                    .thread
+                    .join()
+                    .map_err(|_| anyhow!("Recording thread panicked"))?
+            }
+            None => Ok(vec![]),
+        }
+    }
+}
+
+/// Writes the raw samples coming out of parec into the buffer
+/// until parec exits.
+fn record_pipe(mut stdout: File, mut writer: SegmentedWavWriter) -> Result<Vec<PathBuf>> {
+    let mut last_flush = Instant::now();
+    let mut data = vec![0; 4096];
+    loop {
+        let num_bytes = stdout
+            .read(&mut data)
+            .context("Failed to read the output of parec")?;
+        if num_bytes == 0 {
+            break;
+        }
+        writer.write_bytes(&data[..num_bytes])?;
+        // Keep the header up to date so that the buffer stays
+        // readable even if striputary does not exit cleanly.
+        if last_flush.elapsed() > BUFFER_FLUSH_INTERVAL {
+            writer.flush()?;
+            last_flush = Instant::now();
+        }
+    }
+    Ok(writer.finalize()?)
+}
@@ -0,0 +1,321 @@
+//! Talks to the PulseAudio server (or pipewire-pulse) directly via its
+//! native protocol, so that recording does not depend on pactl and parec.
+
+use std::collections::HashMap;
+use std::ffi::CString;
+use std::io::BufReader;
+use std::io::Read;
+use std::net::Shutdown;
+use std::os::unix::net::UnixStream;
+use std::path::Path;
+use std::path::PathBuf;
+use std::sync::atomic::AtomicBool;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use std::thread::JoinHandle;
+use std::thread::{self};
+use std::time::Instant;
+
+use anyhow::anyhow;
+use anyhow::Context;
+use anyhow::Result;
+use hound::WavSpec;
+use pulseaudio::protocol::ChannelMap;
+use pulseaudio::protocol::Command;
+use pulseaudio::protocol::CommandReply;
+use pulseaudio::protocol::LoadModuleParams;
+use pulseaudio::protocol::MoveStreamParams;
+use pulseaudio::protocol::Prop;
+use pulseaudio::protocol::Props;
+use pulseaudio::protocol::ProtocolError;
+use pulseaudio::protocol::RecordStreamParams;
+use pulseaudio::protocol::SampleFormat;
+use pulseaudio::protocol::SampleSpec;
+use pulseaudio::protocol::TagStructRead;
+use pulseaudio::protocol::TagStructReader;
+use pulseaudio::protocol::TagStructWrite;
+use pulseaudio::protocol::TagStructWriter;
+use pulseaudio::protocol::{self};
+
+use super::recorder::AudioServer;
+use super::recorder::Sink;
+use super::recorder::SinkInput;
+use crate::config::BUFFER_FLUSH_INTERVAL;
+use crate::config::PULSE_CLIENT_NAME;
+use crate::config::RECORDING_NUM_CHANNELS;
+use crate::config::RECORDING_SAMPLE_RATE;
+use crate::wav::SegmentedWavWriter;
+
+/// A connection to the server over which commands are sent.
+struct Connection {
+    socket: BufReader<UnixStream>,
+    protocol_version: u16,
+    seq: u32,
+}
+
+impl Connection {
+    fn new() -> Result<Self> {
+        let socket_path =
+            pulseaudio::socket_path_from_env().context("Failed to find PulseAudio socket")?;
+        let stream = UnixStream::connect(&socket_path)
+            .context(format!("Failed to connect to {:?}", socket_path))?;
+        let cookie = pulseaudio::cookie_path_from_env()
+            .and_then(|path| std::fs::read(path).ok())
+            .unwrap_or_default();
+        let mut conn = Self {
+            socket: BufReader::new(stream),
+            protocol_version: protocol::MAX_VERSION,
+            seq: 0,
+        };
+        let auth_reply: protocol::AuthReply =
+            conn.request(&Command::Auth(protocol::AuthParams {
+                version: protocol::MAX_VERSION,
+                supports_shm: false,
+                supports_memfd: false,
+                cookie,
+            }))?;
+        conn.protocol_version = protocol::MAX_VERSION.min(auth_reply.version);
+        let mut props = Props::new();
+        props.set(Prop::ApplicationName, CString::new(PULSE_CLIENT_NAME)?);
+        let _: protocol::SetClientNameReply = conn.request(&Command::SetClientName(props))?;
+        Ok(conn)
+    }
+
+    fn send(&mut self, command: &Command) -> Result<(), ProtocolError> {
+        self.seq += 1;
+        protocol::write_command_message(
+            self.socket.get_mut(),
+            self.seq,
+            command,
+            self.protocol_version,
+        )
+    }
+
+    fn request<R: CommandReply>(&mut self, command: &Command) -> Result<R> {
+        self.send(command)?;
+        let (_, reply) = protocol::read_reply_message(&mut self.socket, self.protocol_version)?;
+        Ok(reply)
+    }
+
+    fn request_ack(&mut self, command: &Command) -> Result<()> {
+        self.send(command)?;
+        protocol::read_ack_message(&mut self.socket)?;
+        Ok(())
+    }
+}
+
+/// The server replies to LoadModule with the index of the new module,
+/// for which the protocol crate does not provide a type.
+struct LoadModuleReply {
+    index: u32,
+}
+
+impl CommandReply for LoadModuleReply {}
+
+impl TagStructRead for LoadModuleReply {
+    fn read(ts: &mut TagStructReader<'_>, _protocol_version: u16) -> Result<Self, ProtocolError> {
+        Ok(Self {
+            index: ts.read_u32()?,
+        })
+    }
+}
+
+impl TagStructWrite for LoadModuleReply {
+    fn write(
+        &self,
+        w: &mut TagStructWriter<'_>,
+        _protocol_version: u16,
+    ) -> Result<(), ProtocolError> {
+        w.write_u32(self.index)
+    }
+}
+
+struct Recording {
+    stop: Arc<AtomicBool>,
+    socket: UnixStream,
+    thread: JoinHandle<Result<Vec<PathBuf>>>,
+}
+
+pub struct NativePulseServer {
+    conn: Connection,
+    recording: Option<Recording>,
+}
+
+impl NativePulseServer {
+    pub fn connect() -> Result<Self> {
+        Ok(Self {
+            conn: Connection::new()?,
+            recording: None,
+        })
+    }
+}
+
+fn to_string(s: &[u8]) -> String {
+    let s = s.strip_suffix(&[0]).unwrap_or(s);
+    String::from_utf8_lossy(s).into_owned()
+}
+
+impl AudioServer for NativePulseServer {
+    fn list_sink_inputs(&mut self) -> Result<Vec<SinkInput>> {
+        let infos: protocol::SinkInputInfoList =
+            self.conn.request(&Command::GetSinkInputInfoList)?;
+        Ok(infos
+            .into_iter()
+            .map(|info| SinkInput {
+                index: info.index,
+                sink: info.sink_index,
+                properties: info
+                    .props
+                    .iter()
+                    .map(|(key, value)| (key.to_string_lossy().into_owned(), to_string(value)))
+                    .collect::<HashMap<_, _>>(),
+            })
+            .collect())
+    }
+
+    fn get_default_sink(&mut self) -> Result<String> {
+        let info: protocol::ServerInfo = self.conn.request(&Command::GetServerInfo)?;
+        info.default_sink_name
+            .map(|name| name.to_string_lossy().into_owned())
+            .ok_or_else(|| anyhow!("No default sink"))
+    }
+
+    fn list_sinks(&mut self) -> Result<Vec<Sink>> {
+        let sinks: protocol::SinkInfoList = self.conn.request(&Command::GetSinkInfoList)?;
+        Ok(sinks
+            .into_iter()
+            .map(|sink| Sink {
+                index: sink.index,
+                name: sink.name.to_string_lossy().into_owned(),
+                owner_module: sink.owner_module_index,
+            })
+            .collect())
+    }
+
+    fn load_module(&mut self, name: &str, arguments: &str) -> Result<u32> {
+        let reply: LoadModuleReply = self
+            .conn
+            .request(&Command::LoadModule(LoadModuleParams {
+                name: CString::new(name)?,
+                arguments: Some(CString::new(arguments)?),
+            }))
+            .context(format!("Failed to load {}", name))?;
+        Ok(reply.index)
+    }
+
+    fn unload_module(&mut self, index: u32) -> Result<()> {
+        self.conn
+            .request_ack(&Command::UnloadModule(index))
+            .context(format!("Failed to unload module {}", index))
+    }
+
+    fn move_sink_input(&mut self, index: u32, sink_name: &str) -> Result<()> {
+        self.conn
+            .request_ack(&Command::MoveSinkInput(MoveStreamParams {
+                index: Some(index),
+                device_index: None,
+                device_name: Some(CString::new(sink_name)?),
+            }))
+            .context(format!("Failed to move sink input {}", index))
+    }
+
+    fn start_recording(&mut self, source_name: &str, buffer_dir: &Path) -> Result<()> {
+        if self.recording.is_some() {
+            return Err(anyhow!("Already recording"));
+        }
+        // Streams use a connection of their own, so that the data
+        // does not get in the way of replies to further commands.
+        let mut conn = Connection::new()?;
+        let sample_spec = SampleSpec {
+            format: SampleFormat::S16Le,
+            channels: RECORDING_NUM_CHANNELS,
+            sample_rate: RECORDING_SAMPLE_RATE,
+        };
+        let reply: protocol::CreateRecordStreamReply =
+            conn.request(&Command::CreateRecordStream(RecordStreamParams {
+                sample_spec,
+                channel_map: ChannelMap::stereo(),
+                source_name: Some(CString::new(source_name)?),
+                ..Default::default()
+            }))?;
+        if reply.sample_spec != sample_spec {
+            return Err(anyhow!(
+                "Server chose unsupported sample spec {:?}",
+                reply.sample_spec
+            ));
+        }
+        let spec = WavSpec {
+            channels: RECORDING_NUM_CHANNELS as u16,
+            sample_rate: RECORDING_SAMPLE_RATE,
+            bits_per_sample: 16,
+            sample_format: hound::SampleFormat::Int,
+        };
+        let writer = SegmentedWavWriter::new(buffer_dir, spec)
+            .context(format!("Failed to create buffer file in {:?}", buffer_dir))?;
+        let stop = Arc::new(AtomicBool::new(false));
+        let socket = conn.socket.get_ref().try_clone()?;
+        let thread = {
+            let stop = stop.clone();
+            thread::spawn(move || record_stream(conn, reply.channel, writer, &stop))
+        };
+        self.recording = Some(Recording {
+            stop,
+            socket,
+            thread,
+        });
+        Ok(())
+    }
+
+    fn stop_recording(&mut self) -> Result<Vec<PathBuf>> {
+        match self.recording.take() {
+            Some(recording) => {
+                recording.stop.store(true, Ordering::SeqCst);
+                recording.socket.shutdown(Shutdown::Both)?;
+                recording
+                    .thread
+                    .join()
+                    .map_err(|_| anyhow!("Recording thread panicked"))?
+            }
+            None => Ok(vec![]),
+        }
+    }
+}
+
+/// Writes the data arriving on the stream's channel into the buffer
+/// until the socket is shut down.
+fn record_stream(
+    mut conn: Connection,
+    channel: u32,
+    mut writer: SegmentedWavWriter,
+    stop: &AtomicBool,
+) -> Result<Vec<PathBuf>> {
+    let mut last_flush = Instant::now();
+    let mut data = vec![];
+    loop {
+        let descriptor = match protocol::read_descriptor(&mut conn.socket) {
+            Ok(descriptor) => descriptor,
+            Err(_) if stop.load(Ordering::SeqCst) => break,
+            Err(e) => return Err(e).context("Connection to audio server lost while recording"),
+        };
+        data.resize(descriptor.length as usize, 0);
+        if let Err(e) = conn.socket.read_exact(&mut data) {
+            if stop.load(Ordering::SeqCst) {
+                break;
+            }
+            return Err(e).context("Connection to audio server lost while recording");
+        }
+        // Packets on other channels are commands from the server,
+        // such as notifications about the stream, which we ignore.
+        if descriptor.channel != channel {
+            continue;
+        }
+        writer.write_bytes(&data)?;
+        // Keep the header up to date so that the buffer stays
+        // readable even if striputary does not exit cleanly.
+        if last_flush.elapsed() > BUFFER_FLUSH_INTERVAL {
+            writer.flush()?;
+            last_flush = Instant::now();
+        }
+    }
+    Ok(writer.finalize()?)
+}
@@ -1,15 +1,14 @@
+use std::collections::HashMap;
 use std::path::Path;
-use std::process::Command;
-use std::process::Output;
+use std::path::PathBuf;
 
 use anyhow::anyhow;
 use anyhow::Context;
 use anyhow::Result;
-use regex::Captures;
-use regex::Regex;
-use subprocess::Exec;
-use subprocess::Popen;
 
+use super::interrupt;
+use super::pactl::PactlServer;
+use super::pulse_native::NativePulseServer;
 use crate::config::STRIPUTARY_MONITOR_SINK_DESCRIPTION;
 use crate::config::STRIPUTARY_MONITOR_SINK_NAME;
 use crate::config::STRIPUTARY_SINK_DESCRIPTION;
@@ -17,159 +16,238 @@ use crate::config::STRIPUTARY_SINK_NAME;
 use crate::service_config::ServiceConfig;
 use crate::sink_type::SinkType;
 
-fn run_command_and_assert_success(command: &mut Command) -> Result<Output> {
-    let output = command.output()?;
-    assert!(output.status.success());
-    Ok(output)
+pub struct SinkInput {
+    pub index: u32,
+    /// The index of the sink the input is currently playing to.
+    pub sink: u32,
+    pub properties: HashMap<String, String>,
 }
 
-pub fn start_recording(
-    output_file: &Path,
-    service_config: &ServiceConfig,
-    sink_type: SinkType,
-) -> Result<Popen> {
-    setup_recording(service_config, sink_type)?;
-    start_recording_command(output_file)
+pub struct Sink {
+    pub index: u32,
+    pub name: String,
+    /// The module which created the sink.
+    pub owner_module: Option<u32>,
 }
 
-pub fn stop_recording(mut recording_handles: Popen) -> Result<()> {
-    recording_handles
-        .terminate()
-        .context("Failed to terminate parec while recording")?;
-    println!("Stopped recording.");
-    Ok(())
+/// The operations on the audio server which are needed for recording.
+pub trait AudioServer: Send {
+    /// Lists all sink inputs along with their properties.
+    fn list_sink_inputs(&mut self) -> Result<Vec<SinkInput>>;
+    fn list_sinks(&mut self) -> Result<Vec<Sink>>;
+    fn get_default_sink(&mut self) -> Result<String>;
+    /// Loads the module and returns its index.
+    fn load_module(&mut self, name: &str, arguments: &str) -> Result<u32>;
+    fn unload_module(&mut self, index: u32) -> Result<()>;
+    fn move_sink_input(&mut self, index: u32, sink_name: &str) -> Result<()>;
+    /// Starts writing the audio of the source into
+    /// buffer segments in the given directory.
+    fn start_recording(&mut self, source_name: &str, buffer_dir: &Path) -> Result<()>;
+    /// Stops recording and returns the buffer segments that were written.
+    fn stop_recording(&mut self) -> Result<Vec<PathBuf>>;
 }
 
-fn start_recording_command(output_file: &Path) -> Result<Popen> {
-    let parec_cmd = Exec::cmd("parec")
-        .arg("-d")
-        .arg(format!("{}.monitor", STRIPUTARY_SINK_NAME))
-        .arg("--file-format=wav")
-        .arg(output_file.to_str().unwrap());
-    parec_cmd
-        .popen()
-        .context("Failed to execute record command - is parec installed?")
-}
-
-fn setup_recording(service_config: &ServiceConfig, sink_type: SinkType) -> Result<()> {
-    if check_sink_exists()? {
-        remove_sink()?;
-    }
-    let output_sink_name = create_sink(sink_type)?;
-    let mb_index = get_sink_input_index(service_config)?;
-    match mb_index {
-        Some(index) => redirect_sink(index, output_sink_name).map(|_| ()),
-        None => Err(anyhow!(
-            "Failed to find sink index for service: {}",
-            service_config.sink_name
-        )),
+/// Connects to the audio server natively if possible.
+/// Otherwise, falls back to using pactl and parec.
+pub fn connect() -> Box<dyn AudioServer> {
+    match NativePulseServer::connect() {
+        Ok(server) => Box::new(server),
+        Err(e) => {
+            println!(
+                "Failed to connect to PulseAudio natively ({:#}). Falling back to pactl.",
+                e
+            );
+            Box::new(PactlServer::default())
+        }
     }
 }
 
-fn redirect_sink(index: i32, output_sink_name: &str) -> Result<()> {
-    run_command_and_assert_success(
-        Command::new("pactl")
-            .arg("move-sink-input")
-            .arg(format!("{}", index))
-            .arg(output_sink_name),
-    )
-    .map(|_| ())
-    .context("Failed to execute sink redirection via pactl move-sink-input - is pactl installed?")
+/// A sink input which was moved to the striputary sink, along
+/// with the sink it has to be moved back to afterwards.
+struct MovedSinkInput {
+    index: u32,
+    original_sink: String,
 }
 
-fn check_sink_exists() -> Result<bool> {
-    let output = run_command_and_assert_success(Command::new("pactl").arg("list").arg("sinks"))
-        .context("Failed to execute sink list command (pactl list sinks) - is pactl installed?.")?;
-    let stdout = String::from_utf8_lossy(&output.stdout);
-    Ok(stdout.contains(STRIPUTARY_SINK_NAME))
+/// Records the audio of a service. Everything that was changed on the
+/// audio server to do so is undone when the recorder is finished or
+/// dropped, so that the player is audible again even if recording fails.
+pub struct Recorder {
+    server: Box<dyn AudioServer>,
+    loaded_modules: Vec<u32>,
+    moved_sink_input: Option<MovedSinkInput>,
+    is_recording: bool,
+    _interrupt_guard: interrupt::RecordingGuard,
 }
 
-fn get_default_sink_for_monitor() -> String {
-    String::from_utf8(
-        run_command_and_assert_success(Command::new("pactl").arg("get-default-sink"))
-            .expect("Failed to get name of default sink for monitor.")
-            .stdout,
-    )
-    .expect("Failed to decode output of pactl get-default-sink")
-}
+impl Recorder {
+    pub fn start(
+        buffer_dir: &Path,
+        service_config: &ServiceConfig,
+        sink_type: SinkType,
+    ) -> Result<Self> {
+        let mut recorder = Self {
+            server: connect(),
+            loaded_modules: vec![],
+            moved_sink_input: None,
+            is_recording: false,
+            _interrupt_guard: interrupt::RecordingGuard::register(),
+        };
+        recorder.setup(service_config, sink_type)?;
+        recorder
+            .server
+            .start_recording(&format!("{}.monitor", STRIPUTARY_SINK_NAME), buffer_dir)
+            .context("Failed to start recording")?;
+        recorder.is_recording = true;
+        Ok(recorder)
+    }
 
-fn remove_sink() -> Result<()> {
-    run_command_and_assert_success(
-        Command::new("pactl")
-            .arg("unload-module")
-            .arg("module-null-sink"),
-    )
-    .map(|_| ())
-}
+    /// Cleans up and returns the buffer segments that were recorded.
+    pub fn finish(mut self) -> Result<Vec<PathBuf>> {
+        self.cleanup()
+    }
 
-fn create_sink(sink_type: SinkType) -> Result<&'static str> {
-    let output = Command::new("pactl")
-        .arg("load-module")
-        .arg("module-null-sink")
-        .arg(format!("sink_name={}", STRIPUTARY_SINK_NAME))
-        .arg(format!(
-            "sink_properties=device.description={}",
-            STRIPUTARY_SINK_DESCRIPTION
-        ))
-        .output()
-        .context("Failed to execute sink creation command.")?;
-    assert!(output.status.success());
-    if let SinkType::Monitor = sink_type {
-        create_monitor_sink()?;
-        Ok(STRIPUTARY_MONITOR_SINK_NAME)
-    } else {
-        Ok(STRIPUTARY_SINK_NAME)
+    fn setup(&mut self, service_config: &ServiceConfig, sink_type: SinkType) -> Result<()> {
+        self.remove_leftover_sinks()?;
+        let output_sink_name = self.create_sink(sink_type)?;
+        let sink_input = self.get_sink_input(service_config)?.ok_or_else(|| {
+            anyhow!(
+                "Failed to find sink input for service: {}",
+                service_config.sink_input
+            )
+        })?;
+        let original_sink = self
+            .server
+            .list_sinks()?
+            .into_iter()
+            .find(|sink| sink.index == sink_input.sink)
+            .map(|sink| sink.name)
+            .ok_or_else(|| anyhow!("Failed to find sink of sink input {}", sink_input.index))?;
+        self.server
+            .move_sink_input(sink_input.index, output_sink_name)
+            .context("Failed to redirect sink input")?;
+        self.moved_sink_input = Some(MovedSinkInput {
+            index: sink_input.index,
+            original_sink,
+        });
+        Ok(())
     }
-}
 
-fn create_monitor_sink() -> Result<()> {
-    let output = Command::new("pactl")
-        .arg("load-module")
-        .arg("module-combine-sink")
-        .arg(format!("sink_name={}", STRIPUTARY_MONITOR_SINK_NAME))
-        .arg(format!(
-            "sink_properties=device.description={}",
-            STRIPUTARY_MONITOR_SINK_DESCRIPTION
-        ))
-        .arg(format!(
-            "slaves={},{}",
-            STRIPUTARY_SINK_NAME,
-            get_default_sink_for_monitor()
-        ))
-        .output()
-        .context("Failed to execute sink creation command.")?;
-    assert!(output.status.success());
-    Ok(())
-}
+    /// Removes the sinks of a previous run of striputary
+    /// which did not get the chance to clean up.
+    fn remove_leftover_sinks(&mut self) -> Result<()> {
+        let modules: Vec<_> = self
+            .server
+            .list_sinks()?
+            .into_iter()
+            .filter(|sink| {
+                sink.name == STRIPUTARY_SINK_NAME || sink.name == STRIPUTARY_MONITOR_SINK_NAME
+            })
+            .filter_map(|sink| sink.owner_module)
+            .collect();
+        for module in modules {
+            self.server
+                .unload_module(module)
+                .context("Failed to remove existing sink")?;
+        }
+        Ok(())
+    }
 
-fn get_sink_input_index(service_config: &ServiceConfig) -> Result<Option<i32>> {
-    let output = Command::new("pactl")
-        .arg("list")
-	.arg("sink-inputs")
-        .output()
-        .context("Failed to execute list sink inputs command.")?;
-    assert!(output.status.success());
-    let stdout = String::from_utf8_lossy(&output.stdout);
-    let stdout_without_newlines = stdout.replace("\n", "");
-    let re = Regex::new("Sink Input #([0-9]*).*?media.name = \"(.*?)\"").unwrap();
-    let captures = re.captures_iter(&stdout_without_newlines);
-    let mut temp = captures.filter(|capture| {
-        get_sink_source_name_from_pacmd_output_capture(capture)
-            .map(|name| name == service_config.sink_name)
-            .unwrap_or(false)
-    });
-    temp.next()
-        .map(|capture| get_sink_index_from_pacmd_output_capture(&capture))
-        .transpose()
-}
+    fn create_sink(&mut self, sink_type: SinkType) -> Result<&'static str> {
+        self.load_module(
+            "module-null-sink",
+            &format!(
+                "sink_name={} sink_properties=device.description={}",
+                STRIPUTARY_SINK_NAME, STRIPUTARY_SINK_DESCRIPTION
+            ),
+        )
+        .context("Failed to create sink")?;
+        if let SinkType::Monitor = sink_type {
+            self.create_monitor_sink()?;
+            Ok(STRIPUTARY_MONITOR_SINK_NAME)
+        } else {
+            Ok(STRIPUTARY_SINK_NAME)
+        }
+    }
+
+    fn create_monitor_sink(&mut self) -> Result<()> {
+        let default_sink = self
+            .server
+            .get_default_sink()
+            .context("Failed to get name of default sink for monitor")?;
+        self.load_module(
+            "module-combine-sink",
+            &format!(
+                "sink_name={} sink_properties=device.description={} slaves={},{}",
+                STRIPUTARY_MONITOR_SINK_NAME,
+                STRIPUTARY_MONITOR_SINK_DESCRIPTION,
+                STRIPUTARY_SINK_NAME,
+                default_sink
+            ),
+        )
+        .context("Failed to create monitor sink")
+    }
+
+    fn load_module(&mut self, name: &str, arguments: &str) -> Result<()> {
+        let index = self.server.load_module(name, arguments)?;
+        self.loaded_modules.push(index);
+        Ok(())
+    }
+
+    fn get_sink_input(&mut self, service_config: &ServiceConfig) -> Result<Option<SinkInput>> {
+        let matcher = &service_config.sink_input;
+        Ok(self
+            .server
+            .list_sink_inputs()?
+            .into_iter()
+            .find(|sink_input| {
+                sink_input
+                    .properties
+                    .get(matcher.property())
+                    .map(|value| &value[..])
+                    == Some(matcher.value())
+            }))
+    }
 
-fn get_sink_index_from_pacmd_output_capture(capture: &Captures) -> Result<i32> {
-    let sink_source_index = capture.get(1).context("Invalid line")?.as_str();
-    sink_source_index
-        .parse::<i32>()
-        .context("Integer conversion failed for sink index")
+    /// Stops recording, moves the sink input back and unloads the
+    /// modules we loaded. Continues after failures, so that as much
+    /// as possible is cleaned up, and returns the first error.
+    fn cleanup(&mut self) -> Result<Vec<PathBuf>> {
+        let mut result = Ok(());
+        let mut segments = vec![];
+        if self.is_recording {
+            self.is_recording = false;
+            match self.server.stop_recording() {
+                Ok(files) => {
+                    println!("Stopped recording.");
+                    segments = files;
+                }
+                Err(e) => result = Err(e.context("Failed to stop recording")),
+            }
+        }
+        if let Some(sink_input) = self.moved_sink_input.take() {
+            result = result.and(
+                self.server
+                    .move_sink_input(sink_input.index, &sink_input.original_sink)
+                    .context("Failed to move sink input back to its original sink"),
+            );
+        }
+        // The combine sink depends on the null sink, so unload in reverse order.
+        while let Some(module) = self.loaded_modules.pop() {
+            result = result.and(
+                self.server
+                    .unload_module(module)
+                    .context(format!("Failed to unload module {}", module)),
+            );
+        }
+        result.map(|_| segments)
+    }
 }
 
-fn get_sink_source_name_from_pacmd_output_capture<'a>(capture: &'a Captures) -> Result<&'a str> {
-    Ok(capture.get(2).context("Invalid line")?.as_str())
+impl Drop for Recorder {
+    fn drop(&mut self) {
+        if let Err(e) = self.cleanup() {
+            println!("{:?}", e);
+        }
+    }
 }
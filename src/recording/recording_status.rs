@@ -3,10 +3,5 @@ pub enum RecordingExitStatus {
     FinishedOrInterrupted,
     AlbumFinished,
     NoNewSongForTooLong,
-}
-
-#[derive(PartialEq)]
-pub enum RecordingStatus {
-    Running,
-    Finished(RecordingExitStatus),
+    PausedForTooLong,
 }
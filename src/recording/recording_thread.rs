@@ -1,10 +1,11 @@
 use std::fs::create_dir_all;
 use std::path::Path;
+use std::path::PathBuf;
 use std::sync::atomic::AtomicBool;
 use std::sync::atomic::Ordering;
 use std::sync::mpsc::Sender;
 use std::sync::Arc;
-use std::thread::{self};
+use std::time::Duration;
 use std::time::Instant;
 
 use anyhow::anyhow;
@@ -13,17 +14,23 @@ use anyhow::Result;
 
 use super::dbus::collect_dbus_info;
 use super::dbus::previous_song;
+use super::dbus::rewind;
 use super::dbus::start_playback;
 use super::dbus::stop_playback;
+use super::interrupt;
+use super::interrupt::sleep;
+use super::recorder::Recorder;
 use super::recording_status::RecordingExitStatus;
 use crate::config;
 use crate::config::TIME_AFTER_SESSION_END;
 use crate::config::TIME_BEFORE_SESSION_START;
 use crate::config::TIME_BETWEEN_SUBSEQUENT_DBUS_COMMANDS;
 use crate::config::WAIT_TIME_BEFORE_FIRST_SONG;
+use crate::cover_art::CoverArtDownloader;
+use crate::pause_mode::PauseMode;
+use crate::playback_event::PlaybackEvent;
+use crate::playback_event::PlaybackStatus;
 use crate::recording::dbus::next_song;
-use crate::recording::recorder;
-use crate::recording::recording_status::RecordingStatus;
 use crate::recording_session::RecordingSession;
 use crate::run_args::RunArgs;
 use crate::song::Song;
@@ -51,21 +58,32 @@ impl RecordingThread {
 
     fn internal_record_new_session(&self) -> Result<(RecordingExitStatus, RecordingSession)> {
         create_dir_all(&self.run_args.session_dir).context("Failed to create session directory")?;
-        if self.run_args.get_buffer_file().exists() {
+        if self.run_args.get_first_buffer_segment().exists() {
             return Err(anyhow!(
                 "Buffer file already exists, not recording a new session."
             ));
         }
-        let recording_handles = recorder::start_recording(
-            &self.run_args.get_buffer_file(),
+        // If anything below fails, dropping the recorder restores the audio setup.
+        let recorder = Recorder::start(
+            &self.run_args.session_dir,
             &self.run_args.service_config,
             self.run_args.sink_type.clone(),
         )?;
         let record_start_time = Instant::now();
-        let (status, session) =
-            self.polling_loop(&self.run_args.get_yaml_file(), &record_start_time)?;
-        recorder::stop_recording(recording_handles)?;
+        let cover_art_downloader =
+            CoverArtDownloader::new(self.run_args.session_dir.join(config::DEFAULT_COVER_DIR));
+        let (status, mut session) = self.polling_loop(
+            &self.run_args.get_yaml_file(),
+            &record_start_time,
+            &cover_art_downloader,
+        )?;
+        session.buffer_segments = recorder
+            .finish()?
+            .iter()
+            .filter_map(|file| file.file_name().map(PathBuf::from))
+            .collect();
         session.save()?;
+        cover_art_downloader.finish();
         Ok((status, session))
     }
 
@@ -73,29 +91,38 @@ impl RecordingThread {
         &self,
         session_file: &Path,
         record_start_time: &Instant,
+        cover_art_downloader: &CoverArtDownloader,
     ) -> Result<(RecordingExitStatus, RecordingSession)> {
         self.initial_buffer_phase()?;
-        let (status, session) = self.recording_phase(session_file, record_start_time)?;
+        let (status, session) =
+            self.recording_phase(session_file, record_start_time, cover_art_downloader)?;
         self.final_buffer_phase();
         Ok((status, session))
     }
 
     fn initial_buffer_phase(&self) -> Result<()> {
-        // Go to next song and back. This helps with missing metadata
-        // for the first track in some configurations.
-        next_song(&self.run_args.service_config)?;
-        thread::sleep(TIME_BETWEEN_SUBSEQUENT_DBUS_COMMANDS);
-        previous_song(&self.run_args.service_config)?;
+        let service_config = &self.run_args.service_config;
+        if !service_config.quirks.skip_metadata_refresh {
+            // Go to next song and back. This helps with missing metadata
+            // for the first track in some configurations.
+            next_song(service_config)?;
+            sleep(TIME_BETWEEN_SUBSEQUENT_DBUS_COMMANDS);
+            previous_song(service_config)?;
+        }
         // Add a small time buffer before starting the playback properly.
         // This ensures that something starts playing, thus registering the
         // pulse audio sink. Also it avoids overflows when calculating the offset
         println!("Begin pre-session phase");
-        start_playback(&self.run_args.service_config)?;
-        thread::sleep(TIME_BEFORE_SESSION_START);
-        stop_playback(&self.run_args.service_config)?;
+        start_playback(service_config)?;
+        sleep(TIME_BEFORE_SESSION_START);
+        stop_playback(service_config)?;
         println!("Go to beginning of song");
-        previous_song(&self.run_args.service_config)?;
-        thread::sleep(WAIT_TIME_BEFORE_FIRST_SONG);
+        if service_config.quirks.rewind_by_seeking {
+            rewind(service_config)?;
+        } else {
+            previous_song(service_config)?;
+        }
+        sleep(WAIT_TIME_BEFORE_FIRST_SONG);
         Ok(())
     }
 
@@ -103,26 +130,88 @@ impl RecordingThread {
         &self,
         session_file: &Path,
         record_start_time: &Instant,
+        cover_art_downloader: &CoverArtDownloader,
     ) -> Result<(RecordingExitStatus, RecordingSession)> {
         let recording_start_time = Instant::now()
             .duration_since(*record_start_time)
             .as_secs_f64();
-        let mut session = RecordingSession::new(&session_file, recording_start_time);
+        let mut session = RecordingSession::new(session_file, recording_start_time);
         println!("Start playback.");
         start_playback(&self.run_args.service_config)?;
         let mut time_last_dbus_signal = Instant::now();
+        let mut pause_start: Option<Instant> = None;
         loop {
+            if interrupt::is_interrupted() {
+                stop_playback(&self.run_args.service_config)?;
+                return Ok((RecordingExitStatus::FinishedOrInterrupted, session));
+            }
             let num_songs_before = session.songs.len();
-            let playback_status = collect_dbus_info(&mut session, &self.run_args.service_config)?;
+            let num_events_before = session.playback_events.len();
+            collect_dbus_info(
+                &mut session,
+                &self.run_args.service_config,
+                record_start_time,
+            )?;
             let num_songs_after = session.songs.len();
-            if let RecordingStatus::Finished(exit_status) = playback_status {
-                return Ok((exit_status, session));
-            }
             // There should only be one new song if the delay between dbus signals is not too large, but you never know
             for song_index in num_songs_before..num_songs_after {
-                self.add_new_song(session.songs[song_index].clone());
+                let song = &session.songs[song_index];
+                cover_art_downloader.download(song);
+                self.add_new_song(song.clone());
                 time_last_dbus_signal = Instant::now();
             }
+            let pause_mode = self.run_args.pause_mode;
+            // Copied, since handling the events modifies the session.
+            let new_events: Vec<PlaybackEvent> =
+                session.playback_events[num_events_before..].to_vec();
+            for event in new_events {
+                match event {
+                    PlaybackEvent::Seeked { position, .. } => {
+                        // The current song now ends at a different time.
+                        time_last_dbus_signal = Instant::now()
+                            .checked_sub(Duration::from_secs_f64(position.max(0.0)))
+                            .unwrap_or_else(Instant::now);
+                    }
+                    PlaybackEvent::StatusChanged {
+                        status: PlaybackStatus::Paused,
+                        ..
+                    } if pause_mode == PauseMode::EndSession => {
+                        return Ok((RecordingExitStatus::FinishedOrInterrupted, session));
+                    }
+                    PlaybackEvent::StatusChanged {
+                        status: PlaybackStatus::Stopped,
+                        ..
+                    } if pause_mode == PauseMode::Split => {
+                        return Ok((RecordingExitStatus::FinishedOrInterrupted, session));
+                    }
+                    PlaybackEvent::StatusChanged {
+                        status: PlaybackStatus::Paused,
+                        ..
+                    } => {
+                        println!("Playback paused.");
+                        pause_start = pause_start.or_else(|| Some(Instant::now()));
+                    }
+                    PlaybackEvent::StatusChanged {
+                        time,
+                        status: PlaybackStatus::Playing,
+                    } => {
+                        if let Some(pause_start) = pause_start.take() {
+                            println!("Playback resumed. Starting new segment.");
+                            // The current song was delayed by the pause.
+                            time_last_dbus_signal += pause_start.elapsed();
+                            session.segment_starts.push(time);
+                            session.save()?;
+                        }
+                    }
+                    _ => {}
+                }
+            }
+            if let Some(pause_start) = pause_start {
+                if pause_start.elapsed() > config::MAX_PAUSE_DURATION {
+                    return Ok((RecordingExitStatus::PausedForTooLong, session));
+                }
+                continue;
+            }
             if let Some(song) = session.songs.last() {
                 let time_elapsed_after_estimated_song_ending = Instant::now()
                     .duration_since(time_last_dbus_signal)
@@ -142,7 +231,10 @@ impl RecordingThread {
     }
 
     fn final_buffer_phase(&self) {
+        if interrupt::is_interrupted() {
+            return;
+        }
         println!("Recording finished. Record final buffer for a few seconds");
-        thread::sleep(TIME_AFTER_SESSION_END);
+        sleep(TIME_AFTER_SESSION_END);
     }
 }
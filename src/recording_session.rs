@@ -5,11 +5,18 @@ use std::vec::Vec;
 
 use anyhow::Context;
 use anyhow::Result;
+use chrono::Local;
 use serde::Deserialize;
 use serde::Serialize;
 
 use crate::config;
+use crate::cut_history::CutHistory;
+use crate::cut_scoring::CutScoring;
+use crate::playback_event::PlaybackEvent;
 use crate::song::Song;
+use crate::util::get_folders;
+use crate::wav::get_segment_file_name;
+use crate::wav_repair::repair_wav_header;
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct RecordingSession {
@@ -17,6 +24,35 @@ pub struct RecordingSession {
     pub filename: PathBuf,
     pub songs: Vec<Song>,
     pub estimated_time_first_song: f64,
+    /// The cut times (in seconds since the start of the buffer)
+    /// that were confirmed by the user.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cut_times: Option<Vec<f64>>,
+    /// The indices of the confirmed cut times which were moved
+    /// independently of the others.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub individually_moved_cuts: Vec<usize>,
+    /// The previous cut times, so that edits can be undone after restarting.
+    #[serde(default, skip_serializing_if = "CutHistory::is_empty")]
+    pub cut_history: CutHistory,
+    /// The files (relative to the session directory) into which the
+    /// audio was recorded, in order. Empty for sessions which were
+    /// recorded into a single buffer file.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub buffer_segments: Vec<PathBuf>,
+    /// How the cuts of this session are determined, if
+    /// different from the default.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cut_scoring: Option<CutScoring>,
+    /// The song changes, seeks and playback status changes while
+    /// recording. Used to find songs which were not played fully.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub playback_events: Vec<PlaybackEvent>,
+    /// The times (in seconds since the start of the recording) at
+    /// which the playback was resumed after a pause. Each of them
+    /// begins a new segment, whose offset is determined separately.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub segment_starts: Vec<f64>,
 }
 
 impl RecordingSession {
@@ -25,21 +61,57 @@ impl RecordingSession {
             filename: path.to_owned(),
             estimated_time_first_song,
             songs: vec![],
+            cut_times: None,
+            individually_moved_cuts: vec![],
+            cut_history: CutHistory::default(),
+            buffer_segments: vec![],
+            cut_scoring: None,
+            playback_events: vec![],
+            segment_starts: vec![],
         }
     }
 
-    pub fn get_buffer_file(&self) -> PathBuf {
-        self.filename
-            .parent()
-            .unwrap()
-            .join(config::DEFAULT_BUFFER_FILE)
+    fn get_dir(&self) -> &Path {
+        self.filename.parent().unwrap()
+    }
+
+    /// The files containing the recorded audio, in order.
+    pub fn get_buffer_files(&self) -> Vec<PathBuf> {
+        let dir = self.get_dir();
+        if !self.buffer_segments.is_empty() {
+            return self
+                .buffer_segments
+                .iter()
+                .map(|segment| dir.join(segment))
+                .collect();
+        }
+        let buffer_file = dir.join(config::DEFAULT_BUFFER_FILE);
+        if buffer_file.exists() {
+            return vec![buffer_file];
+        }
+        // The session file may not list the segments if
+        // striputary did not exit cleanly while recording.
+        get_segment_files(dir)
+    }
+
+    /// Repairs the headers of buffer files which were not finalized
+    /// while recording. Returns the files that were repaired.
+    pub fn repair_buffer_files(&self) -> Result<Vec<PathBuf>> {
+        let mut repaired = vec![];
+        for file in self.get_buffer_files() {
+            if repair_wav_header(&file)? {
+                repaired.push(file);
+            }
+        }
+        Ok(repaired)
     }
 
     pub fn get_music_dir(&self) -> PathBuf {
-        self.filename
-            .parent()
-            .unwrap()
-            .join(Path::new(config::DEFAULT_MUSIC_DIR))
+        self.get_dir().join(Path::new(config::DEFAULT_MUSIC_DIR))
+    }
+
+    pub fn get_cover_dir(&self) -> PathBuf {
+        self.get_dir().join(Path::new(config::DEFAULT_COVER_DIR))
     }
 
     pub fn save(&self) -> Result<()> {
@@ -59,3 +131,25 @@ impl RecordingSession {
         Self::from_file(&dirname.join(config::DEFAULT_SESSION_FILE))
     }
 }
+
+/// The buffer segments in the directory, in order.
+fn get_segment_files(dir: &Path) -> Vec<PathBuf> {
+    (1..)
+        .map(|num| dir.join(get_segment_file_name(num)))
+        .take_while(|file| file.exists())
+        .collect()
+}
+
+/// Returns all session directories within the output directory,
+/// with the most recent session first.
+pub fn get_session_dirs(output_dir: &Path) -> Result<Vec<PathBuf>> {
+    let mut dirs = get_folders(output_dir)?;
+    dirs.sort();
+    dirs.reverse();
+    Ok(dirs)
+}
+
+pub fn get_new_session_dir(output_dir: &Path) -> PathBuf {
+    let date_string = Local::now().format("%Y-%m-%d-%H-%M-%S").to_string();
+    output_dir.join(date_string)
+}
@@ -2,22 +2,31 @@ use std::path::Path;
 use std::path::PathBuf;
 
 use crate::config;
+use crate::pause_mode::PauseMode;
 use crate::service_config::ServiceConfig;
 use crate::sink_type::SinkType;
+use crate::wav::get_segment_file_name;
 
 #[derive(Clone)]
 pub struct RunArgs {
     pub session_dir: PathBuf,
     pub service_config: ServiceConfig,
     pub sink_type: SinkType,
+    pub pause_mode: PauseMode,
 }
 
 impl RunArgs {
-    pub fn new(session_dir: &Path, service_config: ServiceConfig, sink_type: SinkType) -> Self {
+    pub fn new(
+        session_dir: &Path,
+        service_config: ServiceConfig,
+        sink_type: SinkType,
+        pause_mode: PauseMode,
+    ) -> Self {
         Self {
             session_dir: session_dir.into(),
             service_config,
             sink_type,
+            pause_mode,
         }
     }
 
@@ -25,7 +34,7 @@ impl RunArgs {
         self.session_dir.join(config::DEFAULT_SESSION_FILE)
     }
 
-    pub fn get_buffer_file(&self) -> PathBuf {
-        self.session_dir.join(config::DEFAULT_BUFFER_FILE)
+    pub fn get_first_buffer_segment(&self) -> PathBuf {
+        self.session_dir.join(get_segment_file_name(1))
     }
 }
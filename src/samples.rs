@@ -0,0 +1,137 @@
+use hound::SampleFormat;
+use hound::WavSpec;
+
+/// Interleaved samples as stored in the buffer. Integer samples of any
+/// bit depth are kept in an i32 so that no precision is lost.
+#[derive(Clone)]
+pub enum Samples {
+    Int(Vec<i32>),
+    Float(Vec<f32>),
+}
+
+impl Samples {
+    pub fn new(spec: WavSpec, capacity: usize) -> Self {
+        match spec.sample_format {
+            SampleFormat::Int => Self::Int(Vec::with_capacity(capacity)),
+            SampleFormat::Float => Self::Float(Vec::with_capacity(capacity)),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        match self {
+            Self::Int(samples) => samples.len(),
+            Self::Float(samples) => samples.len(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// The sample scaled to the range from -1 to 1.
+    pub fn get_normalized(&self, index: usize, spec: WavSpec) -> Option<f32> {
+        match self {
+            Self::Int(samples) => samples
+                .get(index)
+                .map(|sample| *sample as f32 / get_int_scale(spec.bits_per_sample)),
+            Self::Float(samples) => samples.get(index).copied(),
+        }
+    }
+
+    /// Appends the samples of another buffer of the same format.
+    pub fn append(&mut self, other: Samples) {
+        match (self, other) {
+            (Self::Int(samples), Self::Int(other)) => samples.extend(other),
+            (Self::Float(samples), Self::Float(other)) => samples.extend(other),
+            _ => panic!("Cannot append samples of a different format"),
+        }
+    }
+
+    /// Whether all samples of the frame are exactly zero.
+    pub fn is_silent(&self, frame_num: usize, spec: WavSpec) -> bool {
+        let num_channels = spec.channels as usize;
+        let first = frame_num * num_channels;
+        (first..first + num_channels).all(|index| self.get_normalized(index, spec) == Some(0.0))
+    }
+
+    /// The average of the normalized samples of the frame over all
+    /// channels, which works for any channel layout. Channels with
+    /// opposite phase cancel out, so this is only suitable where the
+    /// waveform matters, not for measuring the volume.
+    pub fn get_downmixed(&self, frame_num: usize, spec: WavSpec) -> Option<f32> {
+        self.get_channel_average(frame_num, spec, |sample| sample)
+    }
+
+    /// The absolute value of the normalized samples
+    /// of the frame, averaged over all channels.
+    pub fn get_amplitude(&self, frame_num: usize, spec: WavSpec) -> Option<f32> {
+        self.get_channel_average(frame_num, spec, f32::abs)
+    }
+
+    /// The square of the normalized samples of the
+    /// frame, averaged over all channels.
+    pub fn get_energy(&self, frame_num: usize, spec: WavSpec) -> Option<f32> {
+        self.get_channel_average(frame_num, spec, |sample| sample * sample)
+    }
+
+    fn get_channel_average(
+        &self,
+        frame_num: usize,
+        spec: WavSpec,
+        f: impl Fn(f32) -> f32,
+    ) -> Option<f32> {
+        let num_channels = spec.channels as usize;
+        let first = frame_num * num_channels;
+        (first..first + num_channels)
+            .map(|index| self.get_normalized(index, spec).map(&f))
+            .sum::<Option<f32>>()
+            .map(|sum| sum / num_channels as f32)
+    }
+}
+
+fn get_int_scale(bits_per_sample: u16) -> f32 {
+    (1u64 << (bits_per_sample - 1)) as f32
+}
+
+#[cfg(test)]
+mod tests {
+    use hound::SampleFormat;
+    use hound::WavSpec;
+
+    use super::Samples;
+
+    fn get_spec(bits_per_sample: u16, sample_format: SampleFormat) -> WavSpec {
+        WavSpec {
+            channels: 2,
+            sample_rate: 44100,
+            bits_per_sample,
+            sample_format,
+        }
+    }
+
+    #[test]
+    fn samples_are_normalized() {
+        let spec = get_spec(24, SampleFormat::Int);
+        let samples = Samples::Int(vec![-8388608, 4194304]);
+        assert_eq!(samples.get_normalized(0, spec), Some(-1.0));
+        assert_eq!(samples.get_normalized(1, spec), Some(0.5));
+        assert_eq!(samples.get_normalized(2, spec), None);
+        let spec = get_spec(32, SampleFormat::Float);
+        assert_eq!(
+            Samples::Float(vec![0.25]).get_normalized(0, spec),
+            Some(0.25)
+        );
+    }
+
+    #[test]
+    fn frames_are_averaged_over_channels() {
+        let spec = get_spec(16, SampleFormat::Int);
+        let samples = Samples::Int(vec![16384, -8192, 0, 0]);
+        assert_eq!(samples.get_downmixed(0, spec), Some(0.125));
+        assert_eq!(samples.get_amplitude(0, spec), Some(0.375));
+        assert_eq!(samples.get_energy(0, spec), Some(0.15625));
+        assert!(!samples.is_silent(0, spec));
+        assert!(samples.is_silent(1, spec));
+        assert_eq!(samples.get_downmixed(2, spec), None);
+    }
+}
@@ -1,57 +1,134 @@
 use std::fmt::Display;
-use std::str::FromStr;
 
+use anyhow::anyhow;
 use anyhow::Result;
 use serde::Deserialize;
 use serde::Serialize;
 
 use crate::recording::dbus::get_instance_of_service;
 
-#[derive(Copy, Clone, Debug, Default, Deserialize, Serialize)]
+/// A media player which can be recorded. Besides the built-in
+/// services, users can define their own in the config file.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct Service {
+    pub name: String,
+    /// The MPRIS bus name of the player. For players which append an
+    /// instance id to their bus name, this is the part before the id.
+    pub bus_name: String,
+    /// Identifies the audio stream of the player.
+    #[serde(with = "serde_yaml::with::singleton_map")]
+    pub sink_input: SinkInputMatcher,
+    #[serde(default)]
+    pub quirks: Quirks,
+}
+
+/// Matches a PulseAudio sink input by one of its properties.
+#[derive(Clone, Debug, Deserialize, Serialize)]
 #[serde(rename_all = "snake_case")]
-pub enum Service {
-    #[default]
-    SpotifyNative,
-    SpotifyChromium,
+pub enum SinkInputMatcher {
+    MediaName(String),
+    ApplicationName(String),
+    ApplicationProcessBinary(String),
 }
 
-impl FromStr for Service {
-    type Err = serde_yaml::Error;
+impl SinkInputMatcher {
+    pub fn property(&self) -> &'static str {
+        match self {
+            Self::MediaName(_) => "media.name",
+            Self::ApplicationName(_) => "application.name",
+            Self::ApplicationProcessBinary(_) => "application.process.binary",
+        }
+    }
 
-    fn from_str(s: &str) -> Result<Self, Self::Err> {
-        // This is quite ugly, but ensures that the config file string representation
-        // is the same as in the command line args (which uses the FromStr impl),
-        // without adding any additional dependencies
-        serde_yaml::from_str(s)
+    pub fn value(&self) -> &str {
+        match self {
+            Self::MediaName(value)
+            | Self::ApplicationName(value)
+            | Self::ApplicationProcessBinary(value) => value,
+        }
+    }
+}
+
+impl Display for SinkInputMatcher {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} = \"{}\"", self.property(), self.value())
     }
 }
 
+/// Deviations of a media player from the behaviour that striputary expects.
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+#[serde(default)]
+pub struct Quirks {
+    /// Do not skip to the next song and back before recording. Skipping
+    /// is done since some players only send the metadata of the first song
+    /// after a song change.
+    pub skip_metadata_refresh: bool,
+    /// Rewind the first song by seeking to its beginning instead of
+    /// sending "Previous". Required for players which go to the previous
+    /// song instead of restarting the current one.
+    pub rewind_by_seeking: bool,
+}
+
 impl Display for Service {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        // Similar to the from_str implementation, this is ugly but consistent.
-        write!(f, "{}", serde_yaml::to_string(self).unwrap())
+        write!(f, "{}", self.name)
+    }
+}
+
+impl Service {
+    pub fn builtin() -> Vec<Service> {
+        vec![
+            Service {
+                name: "spotify_native".into(),
+                bus_name: "org.mpris.MediaPlayer2.spotify".into(),
+                sink_input: SinkInputMatcher::MediaName("Spotify".into()),
+                quirks: Quirks::default(),
+            },
+            Service {
+                name: "spotify_chromium".into(),
+                bus_name: "org.mpris.MediaPlayer2.chromium".into(),
+                sink_input: SinkInputMatcher::MediaName("Playback".into()),
+                quirks: Quirks::default(),
+            },
+        ]
+    }
+
+    /// Finds the service with the given name. Services defined
+    /// by the user take precedence over the built-in ones.
+    pub fn from_name(name: &str, user_services: &[Service]) -> Result<Service> {
+        let services: Vec<_> = user_services
+            .iter()
+            .cloned()
+            .chain(Self::builtin())
+            .collect();
+        services
+            .iter()
+            .find(|service| service.name == name)
+            .cloned()
+            .ok_or_else(|| {
+                let names: Vec<_> = services.iter().map(|service| &service.name[..]).collect();
+                anyhow!(
+                    "Unknown service: {}. Available services: {}",
+                    name,
+                    names.join(", ")
+                )
+            })
     }
 }
 
 #[derive(Clone)]
 pub struct ServiceConfig {
-    pub sink_name: String,
+    pub sink_input: SinkInputMatcher,
     pub dbus_bus_name: String,
+    pub quirks: Quirks,
 }
 
 impl ServiceConfig {
-    pub fn from_service(service: Service) -> Result<ServiceConfig> {
-        use Service::*;
-        let config = match service {
-            SpotifyNative => Ok(ServiceConfig {
-                sink_name: "Spotify".to_string(),
-                dbus_bus_name: "org.mpris.MediaPlayer2.spotify".to_string(),
-            }),
-            SpotifyChromium => Ok(ServiceConfig {
-                sink_name: "Playback".to_string(),
-                dbus_bus_name: get_instance_of_service("org.mpris.MediaPlayer2.chromium")?,
-            }),
-        };
-        config
+    pub fn from_service(service: &Service) -> Result<ServiceConfig> {
+        Ok(ServiceConfig {
+            sink_input: service.sink_input.clone(),
+            dbus_bus_name: get_instance_of_service(&service.bus_name)?,
+            quirks: service.quirks.clone(),
+        })
     }
 }
@@ -1,34 +1,57 @@
 use std::fmt;
-use std::path::Path;
-use std::path::PathBuf;
 
 use serde::Deserialize;
 use serde::Serialize;
 
-#[derive(Debug, PartialEq, Serialize, Deserialize, Clone)]
+use crate::path_template;
+use crate::path_template::Sanitization;
+
+#[derive(Debug, Default, PartialEq, Serialize, Deserialize, Clone)]
 pub struct Song {
+    /// The first artist. Used for naming files.
     pub artist: Option<String>,
+    /// All artists, in the order given by the media player.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub artists: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub album_artists: Vec<String>,
     pub album: Option<String>,
     pub title: Option<String>,
     pub track_number: Option<i64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub disc_number: Option<i64>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub genres: Vec<String>,
+    /// Creation date of the song, usually in ISO 8601 format.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub content_created: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub art_url: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub track_id: Option<String>,
     pub length: f64,
 }
 
 impl Song {
-    pub fn get_target_file(&self, music_dir: &Path, num_in_recording: usize) -> PathBuf {
-        let track_number_str = if let Some(track_number) = self.track_number {
-            format!("{:02}", track_number)
+    /// All artists of the song. Falls back to the first artist for
+    /// sessions recorded before all artists were stored.
+    pub fn all_artists(&self) -> Vec<String> {
+        if self.artists.is_empty() {
+            self.artist.iter().cloned().collect()
         } else {
-            format!("recording_{}", num_in_recording)
-        };
-        let file_name = format!("{}_{}.opus", track_number_str, format_title(&self.title),);
-        self.get_album_folder(music_dir).join(Path::new(&file_name))
+            self.artists.clone()
+        }
+    }
+
+    /// The first album artist, or the first artist if the media
+    /// player does not send album artists.
+    pub fn album_artist(&self) -> Option<&String> {
+        self.album_artists.first().or(self.artist.as_ref())
     }
 
-    pub fn get_album_folder(&self, music_dir: &Path) -> PathBuf {
-        music_dir
-            .join(Path::new(&format_artist(&self.artist)))
-            .join(Path::new(&format_album(&self.album)))
+    pub fn year(&self) -> Option<&str> {
+        let year = self.content_created.as_ref()?.get(..4)?;
+        year.chars().all(|c| c.is_ascii_digit()).then_some(year)
     }
 }
 
@@ -45,20 +68,8 @@ impl fmt::Display for Song {
     }
 }
 
-fn sanitize_string(s: &str) -> String {
-    let first_item = if s.contains(",") {
-        s.split(",").next().unwrap()
-    } else {
-        s
-    };
-    first_item.replace("/", "").replace(" ", "")
-}
-
 fn sanitize_or_default(s: &Option<String>, default: &str) -> String {
-    s.as_ref()
-        .map(|s| sanitize_string(s))
-        .filter(|s| s != "")
-        .unwrap_or(default.into())
+    path_template::sanitize_or_default(s, default, Sanitization::Compact)
 }
 
 pub fn format_title(title: &Option<String>) -> String {
@@ -0,0 +1,419 @@
+use std::ops::Range;
+
+use crate::config::MAX_RESTART_POSITION;
+use crate::config::PLAYBACK_TIME_TOLERANCE;
+use crate::playback_event::PlaybackEvent;
+use crate::playback_event::PlaybackStatus;
+use crate::recording_session::RecordingSession;
+
+/// Where a song is (estimated to be) in the recording. All times
+/// are in seconds since the start of the recording.
+#[derive(Clone, Debug)]
+pub struct SongPlayback {
+    pub start: f64,
+    pub end: f64,
+    /// Whether the song was played from beginning to end without
+    /// interruption, so that it can be cut from the recording.
+    pub complete: bool,
+    /// Whether the song started right where the previous song ended.
+    pub contiguous: bool,
+    /// The times at which the song was paused and resumed again.
+    /// The recording contains silence there.
+    pub pauses: Vec<Pause>,
+}
+
+/// An interruption of a song (in seconds since the start of the recording).
+#[derive(Clone, Debug, PartialEq)]
+pub struct Pause {
+    pub start: f64,
+    pub end: f64,
+}
+
+impl Pause {
+    pub fn length(&self) -> f64 {
+        self.end - self.start
+    }
+}
+
+/// A position in the recording at which a song begins or ends.
+#[derive(Clone, Debug)]
+pub struct CutPoint {
+    /// The estimated time (in seconds since the start of the recording).
+    pub time: f64,
+    /// The index of the song ending at the cut, if that song is complete.
+    pub song_before: Option<usize>,
+    /// The index of the song beginning at the cut, if that song is complete.
+    pub song_after: Option<usize>,
+    /// The index of the song whose playback the time was derived from.
+    pub playback: usize,
+}
+
+/// Determines where the songs of the session were played.
+/// As long as the songs are played contiguously, their positions
+/// follow from the song lengths, which are exact. A song which was
+/// paused and resumed ends later by the length of the pauses. After a
+/// song was skipped, stopped or seeked in, the timeline is re-anchored
+/// at the time of the corresponding MPRIS signal, which is less
+/// precise but is refined by the offset search.
+/// Sessions recorded without playback events are assumed to be
+/// played contiguously.
+pub fn get_song_playbacks(session: &RecordingSession) -> Vec<SongPlayback> {
+    let events = &session.playback_events;
+    let get_change_time = |num: usize| {
+        events.iter().find_map(|event| match event {
+            PlaybackEvent::SongChanged { time, song } if *song == num => Some(*time),
+            _ => None,
+        })
+    };
+    let mut playbacks = vec![];
+    let mut expected_start = Some(session.estimated_time_first_song);
+    for (num, song) in session.songs.iter().enumerate() {
+        let change_time = get_change_time(num);
+        let next_change_time = get_change_time(num + 1);
+        let mut contiguous = num > 0 && expected_start.is_some();
+        let mut start = expected_start
+            .or(change_time)
+            .unwrap_or(session.estimated_time_first_song);
+        let mut complete = true;
+        let mut pauses: Vec<Pause> = vec![];
+        let mut pause_start: Option<f64> = None;
+        let events_during_song = events.iter().filter(|event| {
+            change_time
+                .map(|time| event.time() >= time)
+                .unwrap_or(false)
+                && next_change_time
+                    .map(|time| event.time() < time)
+                    .unwrap_or(true)
+        });
+        for event in events_during_song {
+            match *event {
+                PlaybackEvent::Seeked { time, position } => {
+                    let playing_since = start + get_paused_length(&pauses);
+                    if (time - position - playing_since).abs() < PLAYBACK_TIME_TOLERANCE {
+                        // Some players send these on every song change.
+                        continue;
+                    }
+                    contiguous = false;
+                    start = time - position;
+                    // Starting over makes up for anything that happened before.
+                    complete = position < MAX_RESTART_POSITION;
+                    pauses.clear();
+                    pause_start = None;
+                }
+                PlaybackEvent::StatusChanged {
+                    time,
+                    status: PlaybackStatus::Playing,
+                } => {
+                    // The song continues where it was paused, so it ends later.
+                    if let Some(pause_start) = pause_start.take() {
+                        pauses.push(Pause {
+                            start: pause_start,
+                            end: time,
+                        });
+                    }
+                }
+                PlaybackEvent::StatusChanged { time, status } => {
+                    let end = start + get_paused_length(&pauses) + song.length;
+                    if time < end - PLAYBACK_TIME_TOLERANCE {
+                        match status {
+                            PlaybackStatus::Stopped => complete = false,
+                            _ => pause_start = pause_start.or(Some(time)),
+                        }
+                    }
+                }
+                PlaybackEvent::SongChanged { .. } => {}
+            }
+        }
+        if pause_start.is_some() {
+            // The song was never resumed.
+            complete = false;
+        }
+        let end = start + get_paused_length(&pauses) + song.length;
+        expected_start = match next_change_time {
+            Some(next_change_time) if (next_change_time - end).abs() > PLAYBACK_TIME_TOLERANCE => {
+                // The song was skipped or the player did something
+                // we can not follow, such as pausing after the song.
+                if next_change_time < end {
+                    complete = false;
+                }
+                None
+            }
+            _ => Some(end),
+        };
+        playbacks.push(SongPlayback {
+            start,
+            end,
+            complete,
+            contiguous,
+            pauses,
+        });
+    }
+    playbacks
+}
+
+fn get_paused_length(pauses: &[Pause]) -> f64 {
+    pauses.iter().map(|pause| pause.length()).sum()
+}
+
+/// Returns the positions which have to be cut in order to cut
+/// all complete songs. Contiguous songs share a single cut.
+pub fn get_cut_points(playbacks: &[SongPlayback]) -> Vec<CutPoint> {
+    let mut cut_points = vec![];
+    for (num, playback) in playbacks.iter().enumerate() {
+        let song_after = playback.complete.then_some(num);
+        let previous = num.checked_sub(1).map(|num| (num, &playbacks[num]));
+        match previous {
+            Some((previous_num, previous)) if playback.contiguous => {
+                let song_before = previous.complete.then_some(previous_num);
+                if song_before.is_some() || song_after.is_some() {
+                    cut_points.push(CutPoint {
+                        time: playback.start,
+                        song_before,
+                        song_after,
+                        playback: num,
+                    });
+                }
+            }
+            _ => {
+                if let Some((previous_num, previous)) = previous {
+                    if previous.complete {
+                        cut_points.push(CutPoint {
+                            time: previous.end,
+                            song_before: Some(previous_num),
+                            song_after: None,
+                            playback: previous_num,
+                        });
+                    }
+                }
+                if song_after.is_some() {
+                    cut_points.push(CutPoint {
+                        time: playback.start,
+                        song_before: None,
+                        song_after,
+                        playback: num,
+                    });
+                }
+            }
+        }
+    }
+    if let Some((num, last)) = playbacks.iter().enumerate().next_back() {
+        if last.complete {
+            cut_points.push(CutPoint {
+                time: last.end,
+                song_before: Some(num),
+                song_after: None,
+                playback: num,
+            });
+        }
+    }
+    cut_points
+}
+
+/// Groups the cut points by the segment of the session they are in.
+/// A new segment begins wherever the recording was resumed and
+/// wherever the timeline was re-anchored because a song did not
+/// follow the previous one (after a skip or a seek), since the
+/// offset can differ there. Returns the range of indices of the cut
+/// points in every segment.
+pub fn get_segments(
+    cut_points: &[CutPoint],
+    playbacks: &[SongPlayback],
+    segment_starts: &[f64],
+) -> Vec<Range<usize>> {
+    let get_segment = |cut_point: &CutPoint| {
+        let num_resumptions = segment_starts
+            .iter()
+            .filter(|start| **start <= cut_point.time)
+            .count();
+        let num_anchors = playbacks[..=cut_point.playback]
+            .iter()
+            .filter(|playback| !playback.contiguous)
+            .count();
+        (num_resumptions, num_anchors)
+    };
+    let mut segments: Vec<Range<usize>> = vec![];
+    for (num, cut_point) in cut_points.iter().enumerate() {
+        match segments.last_mut() {
+            Some(segment) if get_segment(&cut_points[segment.start]) == get_segment(cut_point) => {
+                segment.end = num + 1;
+            }
+            _ => segments.push(num..num + 1),
+        }
+    }
+    segments
+}
+
+#[cfg(test)]
+mod tests {
+    use std::ops::Range;
+    use std::path::Path;
+
+    use super::get_cut_points;
+    use super::get_segments;
+    use super::get_song_playbacks;
+    use super::Pause;
+    use crate::playback_event::PlaybackEvent;
+    use crate::playback_event::PlaybackStatus;
+    use crate::recording_session::RecordingSession;
+    use crate::song::Song;
+
+    fn get_session(song_lengths: &[f64], playback_events: Vec<PlaybackEvent>) -> RecordingSession {
+        let mut session = RecordingSession::new(Path::new("session.yaml"), 1.0);
+        session.songs = song_lengths
+            .iter()
+            .map(|length| Song {
+                length: *length,
+                ..Song::default()
+            })
+            .collect();
+        session.playback_events = playback_events;
+        session
+    }
+
+    fn song_changed(time: f64, song: usize) -> PlaybackEvent {
+        PlaybackEvent::SongChanged { time, song }
+    }
+
+    fn status_changed(time: f64, status: PlaybackStatus) -> PlaybackEvent {
+        PlaybackEvent::StatusChanged { time, status }
+    }
+
+    #[test]
+    fn pause_and_resume_shifts_song() {
+        let mut session = get_session(
+            &[100.0, 100.0],
+            vec![
+                song_changed(1.5, 0),
+                status_changed(1.5, PlaybackStatus::Playing),
+                status_changed(50.0, PlaybackStatus::Paused),
+                status_changed(80.0, PlaybackStatus::Playing),
+                song_changed(131.5, 1),
+            ],
+        );
+        session.segment_starts = vec![80.0];
+        let playbacks = get_song_playbacks(&session);
+        assert!(playbacks.iter().all(|playback| playback.complete));
+        assert_eq!(playbacks[0].start, 1.0);
+        assert_eq!(playbacks[0].end, 131.0);
+        assert_eq!(
+            playbacks[0].pauses,
+            vec![Pause {
+                start: 50.0,
+                end: 80.0
+            }]
+        );
+        assert_eq!(playbacks[1].start, 131.0);
+        assert!(playbacks[1].contiguous);
+        let cut_points = get_cut_points(&playbacks);
+        assert_eq!(cut_points.len(), 3);
+        assert_eq!(
+            get_segments(&cut_points, &playbacks, &session.segment_starts),
+            vec![0..1, 1..3]
+        );
+    }
+
+    #[test]
+    fn song_which_is_never_resumed_is_incomplete() {
+        let session = get_session(
+            &[100.0],
+            vec![
+                song_changed(1.5, 0),
+                status_changed(50.0, PlaybackStatus::Paused),
+            ],
+        );
+        let playbacks = get_song_playbacks(&session);
+        assert!(!playbacks[0].complete);
+    }
+
+    #[test]
+    fn stopped_song_is_incomplete() {
+        let session = get_session(
+            &[100.0],
+            vec![
+                song_changed(1.5, 0),
+                status_changed(50.0, PlaybackStatus::Stopped),
+            ],
+        );
+        let playbacks = get_song_playbacks(&session);
+        assert!(!playbacks[0].complete);
+    }
+
+    fn seeked(time: f64, position: f64) -> PlaybackEvent {
+        PlaybackEvent::Seeked { time, position }
+    }
+
+    fn get_cut_times_and_segments(session: &RecordingSession) -> (Vec<f64>, Vec<Range<usize>>) {
+        let playbacks = get_song_playbacks(session);
+        let cut_points = get_cut_points(&playbacks);
+        let segments = get_segments(&cut_points, &playbacks, &session.segment_starts);
+        let times = cut_points.iter().map(|cut_point| cut_point.time).collect();
+        (times, segments)
+    }
+
+    #[test]
+    fn skip_starts_new_segment() {
+        let session = get_session(
+            &[100.0, 100.0, 100.0, 100.0],
+            vec![
+                song_changed(1.5, 0),
+                song_changed(101.5, 1),
+                song_changed(150.0, 2),
+                song_changed(250.0, 3),
+            ],
+        );
+        let playbacks = get_song_playbacks(&session);
+        assert!(!playbacks[1].complete);
+        assert!(!playbacks[2].contiguous);
+        let (times, segments) = get_cut_times_and_segments(&session);
+        assert_eq!(times, vec![1.0, 101.0, 150.0, 250.0, 350.0]);
+        assert_eq!(segments, vec![0..2, 2..5]);
+    }
+
+    #[test]
+    fn seek_starts_new_segment() {
+        let session = get_session(
+            &[100.0, 100.0, 100.0],
+            vec![
+                song_changed(1.5, 0),
+                song_changed(101.5, 1),
+                seeked(130.0, 60.0),
+                song_changed(170.0, 2),
+            ],
+        );
+        let playbacks = get_song_playbacks(&session);
+        assert!(!playbacks[1].complete);
+        assert!(playbacks[2].contiguous);
+        let (times, segments) = get_cut_times_and_segments(&session);
+        assert_eq!(times, vec![1.0, 101.0, 170.0, 270.0]);
+        assert_eq!(segments, vec![0..2, 2..4]);
+    }
+
+    #[test]
+    fn replay_starts_new_segment() {
+        let session = get_session(
+            &[100.0, 100.0, 100.0],
+            vec![
+                song_changed(1.5, 0),
+                song_changed(101.5, 1),
+                seeked(120.0, 0.0),
+                song_changed(220.0, 2),
+            ],
+        );
+        let playbacks = get_song_playbacks(&session);
+        assert!(playbacks.iter().all(|playback| playback.complete));
+        let (times, segments) = get_cut_times_and_segments(&session);
+        assert_eq!(times, vec![1.0, 101.0, 120.0, 220.0, 320.0]);
+        assert_eq!(segments, vec![0..2, 2..5]);
+    }
+
+    #[test]
+    fn contiguous_songs_share_segment() {
+        let session = get_session(
+            &[100.0, 100.0],
+            vec![song_changed(1.5, 0), song_changed(101.5, 1)],
+        );
+        let (times, segments) = get_cut_times_and_segments(&session);
+        assert_eq!(times, vec![1.0, 101.0, 201.0]);
+        assert_eq!(segments, vec![0..3]);
+    }
+}
@@ -1,6 +1,10 @@
-use std::{fs::{self, DirEntry}, path::{Path, PathBuf}};
+use std::fs::DirEntry;
+use std::fs::{self};
+use std::path::Path;
+use std::path::PathBuf;
 
 use anyhow::Result;
+use serde::de::DeserializeOwned;
 
 fn get_entries_with_predicate<F>(
     folder: &Path,
@@ -25,3 +29,11 @@ fn iter_folders(folder: &Path) -> Result<impl Iterator<Item = PathBuf>> {
 pub fn get_folders(folder: &Path) -> Result<Vec<PathBuf>> {
     Ok(iter_folders(folder)?.collect())
 }
+
+/// Parses a value from its name as written in the config file, so
+/// that command line arguments accept the same spelling as the
+/// serde representation. A plain word is a valid YAML scalar which
+/// deserializes into the unit variant of the same name.
+pub fn from_yaml_str<T: DeserializeOwned>(s: &str) -> Result<T, serde_yaml::Error> {
+    serde_yaml::from_str(s)
+}
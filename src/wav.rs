@@ -1,33 +1,114 @@
+use std::fs::File;
+use std::io::BufReader;
+use std::io::BufWriter;
 use std::path::Path;
+use std::path::PathBuf;
+
+use hound::Sample;
+use hound::SampleFormat;
+use hound::WavReader;
+use hound::WavSpec;
+use hound::WavWriter;
 
 use crate::audio_excerpt::AudioExcerpt;
 use crate::audio_time::AudioTime;
-use crate::errors::MissingSongError;
+use crate::config::BUFFER_SEGMENT_LENGTH;
+use crate::config::BUFFER_SEGMENT_PREFIX;
+use crate::errors::ExcerptError;
+use crate::samples::Samples;
+
+/// The name of the n-th buffer segment, counting from 1.
+pub fn get_segment_file_name(num: usize) -> String {
+    format!("{}{:04}.wav", BUFFER_SEGMENT_PREFIX, num)
+}
+
+fn open_buffer_file(file: &Path) -> Result<WavReader<BufReader<File>>, ExcerptError> {
+    let reader = WavReader::open(file).map_err(|error| ExcerptError::UnreadableBuffer {
+        file: file.into(),
+        error,
+    })?;
+    let spec = reader.spec();
+    let is_supported = match spec.sample_format {
+        SampleFormat::Int => (8..=32).contains(&spec.bits_per_sample),
+        SampleFormat::Float => spec.bits_per_sample == 32,
+    };
+    if !is_supported || spec.channels == 0 {
+        return Err(ExcerptError::UnsupportedSampleFormat {
+            file: file.into(),
+            spec,
+        });
+    }
+    Ok(reader)
+}
 
-pub fn get_volume_average_over_channels(samples: Vec<i16>) -> Vec<i16> {
-    samples
-        .chunks_exact(2)
-        .map(|c| (c[0] / 2) + (c[1] / 2))
-        .collect()
+/// Reads up to the given number of samples from the current position.
+fn read_samples<S: Sample>(
+    reader: &mut WavReader<BufReader<File>>,
+    num_samples: usize,
+    samples: &mut Vec<S>,
+) -> Result<(), hound::Error> {
+    for sample in reader.samples::<S>().take(num_samples) {
+        samples.push(sample?);
+    }
+    Ok(())
 }
 
+/// Extracts the audio between the two times from the buffer, which
+/// may be split into multiple segments that are played back to back.
 pub fn extract_audio(
-    file_path: &Path,
+    buffer_files: &[PathBuf],
     start_time: f64,
     end_time: f64,
-) -> Result<AudioExcerpt, MissingSongError> {
-    let mut reader = hound::WavReader::open(file_path).unwrap();
+) -> Result<AudioExcerpt, ExcerptError> {
+    let (first_file, other_files) = buffer_files.split_first().ok_or(ExcerptError::NoBuffer)?;
+    let mut file = first_file;
+    let mut reader = open_buffer_file(file)?;
+    let mut other_files = other_files.iter();
     let spec = reader.spec();
     let start = AudioTime::from_time_and_spec(start_time, spec);
     let end = AudioTime::from_time_and_spec(end_time, spec);
-    let num_samples = (end - start).interleaved_sample_num;
-    reader.seek(start.frame_num)?;
-    let samples_interleaved: Vec<i16> = reader
-        .samples::<i16>()
-        .take(num_samples as usize)
-        .collect::<Result<Vec<_>, hound::Error>>()?;
+    // Compute the length from the frame numbers, so that excerpts
+    // of adjacent time ranges neither overlap nor leave gaps.
+    let num_samples = end.frame_num.saturating_sub(start.frame_num) * spec.channels as u32;
+    let mut samples_interleaved = Samples::new(spec, num_samples as usize);
+    // The frame number within the whole buffer at which the current segment starts
+    let mut segment_start = 0;
+    loop {
+        let segment_end = segment_start + reader.duration();
+        if start.frame_num < segment_end {
+            let read_error = |error: hound::Error| ExcerptError::UnreadableBuffer {
+                file: file.clone(),
+                error,
+            };
+            reader
+                .seek(start.frame_num.saturating_sub(segment_start))
+                .map_err(|error| read_error(error.into()))?;
+            let remaining = num_samples as usize - samples_interleaved.len();
+            match &mut samples_interleaved {
+                Samples::Int(samples) => read_samples(&mut reader, remaining, samples),
+                Samples::Float(samples) => read_samples(&mut reader, remaining, samples),
+            }
+            .map_err(read_error)?;
+        }
+        if samples_interleaved.len() as u32 == num_samples {
+            break;
+        }
+        segment_start = segment_end;
+        match other_files.next() {
+            Some(next_file) => file = next_file,
+            None => break,
+        }
+        reader = open_buffer_file(file)?;
+        if reader.spec() != spec {
+            return Err(ExcerptError::InconsistentSegments { file: file.clone() });
+        }
+    }
     if samples_interleaved.len() as u32 != num_samples {
-        Err(MissingSongError {})
+        Err(ExcerptError::PastEndOfBuffer {
+            start: start_time,
+            end: end_time,
+            buffer_length: segment_start as f64 / spec.sample_rate as f64,
+        })
     } else {
         Ok(AudioExcerpt {
             spec,
@@ -37,3 +118,84 @@ pub fn extract_audio(
         })
     }
 }
+
+/// Writes the recorded audio into a sequence of wav files of limited
+/// length, so that long sessions neither run into the size limit of
+/// the wav format nor risk losing everything if a header is left
+/// unfinished.
+pub struct SegmentedWavWriter {
+    dir: PathBuf,
+    spec: WavSpec,
+    writer: WavWriter<BufWriter<File>>,
+    files: Vec<PathBuf>,
+    num_frames_in_segment: u32,
+    max_frames_per_segment: u32,
+    /// Bytes of an incomplete frame at the end of the previous write
+    pending: Vec<u8>,
+}
+
+impl SegmentedWavWriter {
+    pub fn new(dir: &Path, spec: WavSpec) -> Result<Self, hound::Error> {
+        let file = dir.join(get_segment_file_name(1));
+        Ok(Self {
+            dir: dir.into(),
+            spec,
+            writer: WavWriter::create(&file, spec)?,
+            files: vec![file],
+            num_frames_in_segment: 0,
+            max_frames_per_segment: (BUFFER_SEGMENT_LENGTH.as_secs() * spec.sample_rate as u64)
+                as u32,
+            pending: vec![],
+        })
+    }
+
+    /// Writes raw 16 bit little endian samples, as they arrive from
+    /// the audio server. The data may end in the middle of a frame.
+    pub fn write_bytes(&mut self, data: &[u8]) -> Result<(), hound::Error> {
+        let frame_size = 2 * self.spec.channels as usize;
+        self.pending.extend_from_slice(data);
+        let num_complete_bytes = self.pending.len() - self.pending.len() % frame_size;
+        let samples: Vec<i16> = self
+            .pending
+            .drain(..num_complete_bytes)
+            .collect::<Vec<_>>()
+            .chunks_exact(2)
+            .map(|sample| i16::from_le_bytes([sample[0], sample[1]]))
+            .collect();
+        self.write_samples(&samples)
+    }
+
+    /// Writes interleaved samples. A frame is never split across segments.
+    fn write_samples(&mut self, samples: &[i16]) -> Result<(), hound::Error> {
+        for frame in samples.chunks_exact(self.spec.channels as usize) {
+            if self.num_frames_in_segment >= self.max_frames_per_segment {
+                self.start_new_segment()?;
+            }
+            for sample in frame {
+                self.writer.write_sample(*sample)?;
+            }
+            self.num_frames_in_segment += 1;
+        }
+        Ok(())
+    }
+
+    fn start_new_segment(&mut self) -> Result<(), hound::Error> {
+        let file = self.dir.join(get_segment_file_name(self.files.len() + 1));
+        let writer = WavWriter::create(&file, self.spec)?;
+        std::mem::replace(&mut self.writer, writer).finalize()?;
+        self.files.push(file);
+        self.num_frames_in_segment = 0;
+        Ok(())
+    }
+
+    /// Updates the header of the current segment.
+    pub fn flush(&mut self) -> Result<(), hound::Error> {
+        self.writer.flush()
+    }
+
+    /// Finishes writing and returns the files that were written.
+    pub fn finalize(self) -> Result<Vec<PathBuf>, hound::Error> {
+        self.writer.finalize()?;
+        Ok(self.files)
+    }
+}
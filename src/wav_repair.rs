@@ -0,0 +1,225 @@
+//! If recording is not stopped cleanly, the sizes in the header of the
+//! buffer file are not updated, so that the file appears to contain
+//! less audio than it does (usually none). As long as nothing follows
+//! the audio data, the correct sizes can be recovered from the size
+//! of the file.
+
+use std::fs::File;
+use std::fs::OpenOptions;
+use std::io::Read;
+use std::io::Seek;
+use std::io::SeekFrom;
+use std::io::Write;
+use std::path::Path;
+
+use anyhow::anyhow;
+use anyhow::Context;
+use anyhow::Result;
+
+/// The size of a chunk header, consisting of the chunk id and its size.
+const CHUNK_HEADER_SIZE: u64 = 8;
+/// The offset of the first chunk, after the RIFF header and the WAVE id.
+const FIRST_CHUNK_OFFSET: u64 = 12;
+
+struct DataChunk {
+    /// The offset of the size field of the data chunk
+    size_offset: u64,
+    declared_size: u32,
+    block_align: u16,
+}
+
+fn read_u32(file: &mut File) -> Result<u32> {
+    let mut bytes = [0; 4];
+    file.read_exact(&mut bytes)?;
+    Ok(u32::from_le_bytes(bytes))
+}
+
+fn read_id(file: &mut File) -> Result<[u8; 4]> {
+    let mut id = [0; 4];
+    file.read_exact(&mut id)?;
+    Ok(id)
+}
+
+fn find_data_chunk(file: &mut File) -> Result<DataChunk> {
+    file.seek(SeekFrom::Start(0))?;
+    if &read_id(file)? != b"RIFF" {
+        return Err(anyhow!("Not a RIFF file"));
+    }
+    read_u32(file)?;
+    if &read_id(file)? != b"WAVE" {
+        return Err(anyhow!("Not a WAVE file"));
+    }
+    let mut block_align = None;
+    let mut offset = FIRST_CHUNK_OFFSET;
+    loop {
+        file.seek(SeekFrom::Start(offset))?;
+        let id = read_id(file).context("No data chunk found")?;
+        let size = read_u32(file)?;
+        match &id {
+            b"fmt " => {
+                let mut fmt = [0; 14];
+                file.read_exact(&mut fmt)?;
+                block_align = Some(u16::from_le_bytes([fmt[12], fmt[13]]));
+            }
+            b"data" => {
+                return Ok(DataChunk {
+                    size_offset: offset + 4,
+                    declared_size: size,
+                    block_align: block_align.ok_or_else(|| anyhow!("No fmt chunk before data"))?,
+                });
+            }
+            _ => {}
+        }
+        // Chunks are padded to an even size.
+        offset += CHUNK_HEADER_SIZE + size as u64 + size as u64 % 2;
+    }
+}
+
+/// Whether a valid chunk header which fits into the file starts at
+/// the given offset.
+fn is_chunk_at(file: &mut File, offset: u64, file_size: u64) -> Result<bool> {
+    if offset + CHUNK_HEADER_SIZE > file_size {
+        return Ok(false);
+    }
+    file.seek(SeekFrom::Start(offset))?;
+    let id = read_id(file)?;
+    let size = read_u32(file)? as u64;
+    Ok(id.iter().all(|c| c.is_ascii_alphanumeric() || *c == b' ')
+        && offset + CHUNK_HEADER_SIZE + size <= file_size)
+}
+
+/// Whether the header of the file has not been finalized, either
+/// because the data size was never written or because the audio
+/// data runs past the declared end without another chunk after it.
+fn is_unfinalized(file: &mut File, chunk: &DataChunk, file_size: u64) -> Result<bool> {
+    if chunk.declared_size == 0 || chunk.declared_size == u32::MAX {
+        return Ok(true);
+    }
+    let declared_size = chunk.declared_size as u64;
+    let declared_end = chunk.size_offset + 4 + declared_size + declared_size % 2;
+    Ok(file_size > declared_end && !is_chunk_at(file, declared_end, file_size)?)
+}
+
+/// Fixes the sizes in the header of the wav file if it was clearly
+/// not finalized, using the size of the file. Files whose header
+/// looks finalized are left alone. Returns whether the file was
+/// repaired.
+pub fn repair_wav_header(path: &Path) -> Result<bool> {
+    let mut file = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .open(path)
+        .context(format!("Failed to open {:?}", path))?;
+    let chunk = find_data_chunk(&mut file).context(format!("Invalid wav file: {:?}", path))?;
+    let file_size = file.metadata()?.len();
+    if !is_unfinalized(&mut file, &chunk, file_size)? {
+        return Ok(false);
+    }
+    let data_start = chunk.size_offset + 4;
+    let block_align = chunk.block_align.max(1) as u64;
+    // Keep the sizes representable in the header, even if that means
+    // losing whatever audio lies beyond.
+    let max_data_size = u32::MAX as u64 - (data_start - CHUNK_HEADER_SIZE);
+    let data_size = file_size.saturating_sub(data_start).min(max_data_size);
+    let data_size = data_size - data_size % block_align;
+    if chunk.declared_size as u64 == data_size {
+        return Ok(false);
+    }
+    let riff_size = data_start - CHUNK_HEADER_SIZE + data_size;
+    file.seek(SeekFrom::Start(4))?;
+    file.write_all(&(riff_size as u32).to_le_bytes())?;
+    file.seek(SeekFrom::Start(chunk.size_offset))?;
+    file.write_all(&(data_size as u32).to_le_bytes())?;
+    Ok(true)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::repair_wav_header;
+
+    const BLOCK_ALIGN: u16 = 4;
+
+    fn get_wav(data_size: u32, data: &[u8], trailing: &[u8]) -> Vec<u8> {
+        let mut bytes = vec![];
+        let riff_size = 4 + 24 + 8 + data.len() + trailing.len();
+        bytes.extend_from_slice(b"RIFF");
+        bytes.extend_from_slice(&(riff_size as u32).to_le_bytes());
+        bytes.extend_from_slice(b"WAVE");
+        bytes.extend_from_slice(b"fmt ");
+        bytes.extend_from_slice(&16u32.to_le_bytes());
+        bytes.extend_from_slice(&1u16.to_le_bytes());
+        bytes.extend_from_slice(&2u16.to_le_bytes());
+        bytes.extend_from_slice(&44100u32.to_le_bytes());
+        bytes.extend_from_slice(&(44100 * BLOCK_ALIGN as u32).to_le_bytes());
+        bytes.extend_from_slice(&BLOCK_ALIGN.to_le_bytes());
+        bytes.extend_from_slice(&16u16.to_le_bytes());
+        bytes.extend_from_slice(b"data");
+        bytes.extend_from_slice(&data_size.to_le_bytes());
+        bytes.extend_from_slice(data);
+        bytes.extend_from_slice(trailing);
+        bytes
+    }
+
+    fn get_data_size(bytes: &[u8]) -> u32 {
+        u32::from_le_bytes([bytes[40], bytes[41], bytes[42], bytes[43]])
+    }
+
+    fn get_riff_size(bytes: &[u8]) -> u32 {
+        u32::from_le_bytes([bytes[4], bytes[5], bytes[6], bytes[7]])
+    }
+
+    /// Writes the bytes to a temporary file, repairs it and returns
+    /// whether it was repaired along with its new contents.
+    fn repair(name: &str, bytes: &[u8]) -> (bool, Vec<u8>) {
+        let path = std::env::temp_dir().join(format!(
+            "striputary_wav_repair_{}_{}.wav",
+            name,
+            std::process::id()
+        ));
+        fs::write(&path, bytes).unwrap();
+        let repaired = repair_wav_header(&path).unwrap();
+        let contents = fs::read(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        (repaired, contents)
+    }
+
+    #[test]
+    fn truncated_file_is_repaired() {
+        // An interrupted recording with an unwritten data size and
+        // an incomplete last frame.
+        let bytes = get_wav(0, &[1; 42], &[]);
+        let (repaired, contents) = repair("truncated", &bytes);
+        assert!(repaired);
+        assert_eq!(get_data_size(&contents), 40);
+        assert_eq!(get_riff_size(&contents), 4 + 24 + 8 + 40);
+    }
+
+    #[test]
+    fn data_past_declared_end_is_repaired() {
+        let bytes = get_wav(16, &[1; 40], &[]);
+        let (repaired, contents) = repair("past_end", &bytes);
+        assert!(repaired);
+        assert_eq!(get_data_size(&contents), 40);
+    }
+
+    #[test]
+    fn finalized_file_is_unchanged() {
+        let bytes = get_wav(40, &[1; 40], &[]);
+        let (repaired, contents) = repair("finalized", &bytes);
+        assert!(!repaired);
+        assert_eq!(contents, bytes);
+    }
+
+    #[test]
+    fn file_with_trailing_chunk_is_unchanged() {
+        let mut trailing = b"LIST".to_vec();
+        trailing.extend_from_slice(&4u32.to_le_bytes());
+        trailing.extend_from_slice(b"INFO");
+        let bytes = get_wav(40, &[1; 40], &trailing);
+        let (repaired, contents) = repair("trailing_chunk", &bytes);
+        assert!(!repaired);
+        assert_eq!(contents, bytes);
+    }
+}